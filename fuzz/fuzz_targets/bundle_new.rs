@@ -0,0 +1,20 @@
+#![no_main]
+
+use apache_avro::Schema;
+use axer::bundle::{Bundle, BundleItem, ParseOptions};
+use axer::client::TAGS_AVRO_SCHEMA;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+
+    let _ = Bundle::new(data, &schema);
+    let _ = BundleItem::new(data, &schema);
+
+    let options = ParseOptions {
+        max_depth: 2,
+        lenient: true,
+        layout: true,
+    };
+    let _ = Bundle::with_options(data, &schema, &options);
+});
@@ -0,0 +1,236 @@
+use crate::bundle::deep_hash::data_item_signature_data;
+use crate::bundle::tags::{Base64, Base64Tags, Tag};
+use crate::bundle::writer::{encode_item, ItemFields};
+use crate::bundle::BundleItem;
+use crate::utils;
+use apache_avro::Schema;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
+use base64::Engine;
+use ed25519_dalek::Signer as _;
+use rsa::traits::PublicKeyParts;
+use rsa::{BigUint, Pss, RsaPrivateKey};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::path::Path;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("keyfile is neither an arweave jwk nor an ed25519 key: {0}")]
+    KeyfileError(#[from] serde_json::Error),
+    #[error("jwk field is not valid base64: {0}")]
+    Base64Error(#[from] base64::DecodeError),
+    #[error("rsa error: {0}")]
+    RsaError(#[from] rsa::Error),
+    #[error("invalid ed25519 key length {0}, expected 32 or 64 bytes")]
+    Ed25519KeyLength(usize),
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    n: String,
+    e: String,
+    d: String,
+    p: String,
+    q: String,
+}
+
+/// Key used to sign data items.
+pub enum Signer {
+    Arweave(RsaPrivateKey),
+    Ed25519(ed25519_dalek::SigningKey),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Keyfile {
+    Jwk(Jwk),
+    Bytes(Vec<u8>),
+}
+
+impl Signer {
+    /// Loads a keyfile, either an Arweave JWK or an ed25519 key stored as a
+    /// json byte array, holding the 32-byte secret or the 64-byte keypair.
+    pub fn from_keyfile<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let keyfile = std::fs::read_to_string(path)?;
+
+        match serde_json::from_str::<Keyfile>(&keyfile)? {
+            Keyfile::Jwk(jwk) => Self::from_jwk(&jwk),
+            Keyfile::Bytes(bytes) => Self::from_ed25519_bytes(&bytes),
+        }
+    }
+
+    fn from_jwk(jwk: &Jwk) -> Result<Self, Error> {
+        let decode = |field: &str| -> Result<BigUint, Error> {
+            Ok(BigUint::from_bytes_be(&base64.decode(field)?))
+        };
+
+        let key = RsaPrivateKey::from_components(
+            decode(&jwk.n)?,
+            decode(&jwk.e)?,
+            decode(&jwk.d)?,
+            vec![decode(&jwk.p)?, decode(&jwk.q)?],
+        )?;
+
+        Ok(Self::Arweave(key))
+    }
+
+    /// Builds an ed25519 signer from the 32-byte secret, or from a 64-byte
+    /// keypair whose first half is the secret.
+    pub fn from_ed25519_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let secret: [u8; 32] = match bytes.len() {
+            32 | 64 => bytes[..32].try_into().unwrap(),
+            len => return Err(Error::Ed25519KeyLength(len)),
+        };
+
+        Ok(Self::Ed25519(ed25519_dalek::SigningKey::from_bytes(&secret)))
+    }
+
+    pub fn signature_type(&self) -> u16 {
+        match self {
+            Signer::Arweave(_) => 1,
+            Signer::Ed25519(_) => 2,
+        }
+    }
+
+    pub fn owner(&self) -> Vec<u8> {
+        match self {
+            Signer::Arweave(key) => {
+                let mut owner = key.n().to_bytes_be();
+                // The owner is the fixed size modulus, left padded if needed.
+                while owner.len() < key.size() {
+                    owner.insert(0, 0);
+                }
+                owner
+            }
+            Signer::Ed25519(key) => key.verifying_key().to_bytes().to_vec(),
+        }
+    }
+
+    /// Signs the message with RSA-PSS over its SHA-256 digest for Arweave
+    /// keys, or with plain ed25519.
+    pub fn sign(&self, message: &[u8]) -> Result<Vec<u8>, Error> {
+        match self {
+            Signer::Arweave(key) => {
+                let hashed = utils::sha256(message);
+                let mut rng = rand::thread_rng();
+                Ok(key.sign_with_rng(&mut rng, Pss::new::<Sha256>(), &hashed)?)
+            }
+            Signer::Ed25519(key) => Ok(key.sign(message).to_bytes().to_vec()),
+        }
+    }
+}
+
+/// Builds and signs a new data item.
+#[derive(Default)]
+pub struct DataItemBuilder {
+    data: Vec<u8>,
+    target: Option<[u8; 32]>,
+    anchor: Option<[u8; 32]>,
+    tags: Vec<Tag<Base64>>,
+}
+
+impl DataItemBuilder {
+    pub fn new<D: Into<Vec<u8>>>(data: D) -> Self {
+        Self {
+            data: data.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn target(mut self, target: [u8; 32]) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    pub fn anchor(mut self, anchor: [u8; 32]) -> Self {
+        self.anchor = Some(anchor);
+        self
+    }
+
+    pub fn tag(mut self, name: &str, value: &str) -> Self {
+        self.tags.push(Tag {
+            name: Base64::encode(name.as_bytes()),
+            value: Base64::encode(value.as_bytes()),
+        });
+        self
+    }
+
+    /// Signs the item and parses the result back, so the returned item and
+    /// its id are exactly what a bundle parser would produce.
+    pub fn sign(self, signer: &Signer, tags_schema: &Schema) -> Result<BundleItem, super::Error> {
+        let tags = Base64Tags(self.tags);
+        let tag_bytes = tags.to_avro(tags_schema)?;
+
+        let signature_type = signer.signature_type();
+        let owner = signer.owner();
+        let target = self.target.as_ref().map_or(&[][..], |t| &t[..]);
+        let anchor = self.anchor.as_ref().map_or(&[][..], |a| &a[..]);
+
+        let message = data_item_signature_data(
+            signature_type as u64,
+            &owner,
+            target,
+            anchor,
+            &tag_bytes,
+            &self.data,
+        );
+        let signature = signer.sign(&message)?;
+
+        let bytes = encode_item(&ItemFields {
+            signature_type,
+            signature: &signature,
+            owner: &owner,
+            target,
+            anchor,
+            tags_count: tags.0.len(),
+            tags: &tag_bytes,
+            data: &self.data,
+        });
+
+        BundleItem::new(&bytes, tags_schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::Bundle;
+    use crate::client::TAGS_AVRO_SCHEMA;
+
+    const TEST_WALLET: &str = "res/test_wallet.json";
+
+    #[test]
+    fn test_data_item_builder() {
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+
+        let arweave = Signer::from_keyfile(TEST_WALLET).unwrap();
+        let item = DataItemBuilder::new("hello")
+            .target([1; 32])
+            .anchor([2; 32])
+            .tag("Content-Type", "text/plain")
+            .sign(&arweave, &schema)
+            .unwrap();
+
+        assert!(item.verified);
+        assert_eq!(item.signature_type, 1);
+        assert_eq!(item.id, base64.encode(utils::sha256(&base64.decode(&item.signature).unwrap())));
+        assert_eq!(base64.decode(&item.owner).unwrap().len(), 512);
+        assert_eq!(item.tags.get("Content-Type"), Some("text/plain".to_string()));
+
+        let ed25519 = Signer::from_ed25519_bytes(&[7; 64]).unwrap();
+        let untagged = DataItemBuilder::new(vec![0, 1, 2]).sign(&ed25519, &schema).unwrap();
+        assert!(untagged.verified);
+        assert_eq!(untagged.signature_type, 2);
+
+        let bundle = Bundle {
+            items: vec![item, untagged],
+            diagnostics: vec![],
+        };
+        let parsed = Bundle::new(&bundle.to_bytes().unwrap(), &schema).unwrap();
+        assert!(parsed.items == bundle.items);
+
+        assert!(matches!(Signer::from_ed25519_bytes(&[7; 31]), Err(Error::Ed25519KeyLength(31))));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::bundle::Error;
+use crate::utils;
+
+/// Bounds-checked reader over the binary layout of a data item.
+/// Every read names the field it is for, so a truncated item reports which
+/// field was cut and at what offset.
+pub struct ItemCursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ItemCursor<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+        }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn read(&mut self, field: &'static str, length: usize) -> Result<&'a [u8], Error> {
+        let available = self.data.len() - self.pos;
+        if length > available {
+            return Err(Error::ItemFieldIncomplete {
+                field,
+                offset: self.pos,
+                needed: length,
+                available,
+            });
+        }
+
+        let bytes = &self.data[self.pos..self.pos + length];
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    pub fn read_u16(&mut self, field: &'static str) -> Result<u16, Error> {
+        Ok(utils::byte_array_to_u64(self.read(field, 2)?)? as u16)
+    }
+
+    pub fn read_u64(&mut self, field: &'static str) -> Result<u64, Error> {
+        Ok(utils::byte_array_to_u64(self.read(field, 8)?)?)
+    }
+
+    /// Reads a presence flag followed, when it is 1, by a field of the given
+    /// length. Absent fields are returned empty.
+    pub fn read_optional(&mut self, field: &'static str, length: usize) -> Result<&'a [u8], Error> {
+        match self.read(field, 1)?[0] {
+            1 => self.read(field, length),
+            _ => Ok(&[]),
+        }
+    }
+
+    /// Returns everything after the current position.
+    pub fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.pos..];
+        self.pos = self.data.len();
+        rest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_cursor() {
+        let data = [2, 0, 1, 9, 9, 0, 5];
+        let mut cursor = ItemCursor::new(&data);
+
+        assert_eq!(cursor.read_u16("signature type").unwrap(), 2);
+        assert_eq!(cursor.read_optional("target", 2).unwrap(), &[9, 9]);
+        assert!(cursor.read_optional("anchor", 2).unwrap().is_empty());
+        assert_eq!(cursor.position(), 6);
+
+        match cursor.read_u64("tag count") {
+            Err(Error::ItemFieldIncomplete {
+                field,
+                offset,
+                needed,
+                available,
+            }) => assert_eq!((field, offset, needed, available), ("tag count", 6, 8, 1)),
+            _ => panic!("expected incomplete field error"),
+        }
+
+        assert!(matches!(cursor.read("data", usize::MAX), Err(Error::ItemFieldIncomplete { .. })));
+        assert_eq!(cursor.rest(), &[5]);
+        assert!(cursor.rest().is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+use crate::bundle::tags::Tag;
+use crate::utils;
+
+/// A node of the deep-hash input, either raw bytes or a nested list of nodes.
+pub enum DeepHashChunk<'a> {
+    Blob(&'a [u8]),
+    List(Vec<DeepHashChunk<'a>>),
+}
+
+/// Arweave's recursive SHA-384 hash over blobs and lists, used to build the
+/// message that data items and transactions are signed over.
+pub fn deep_hash(chunk: &DeepHashChunk) -> [u8; 48] {
+    match chunk {
+        DeepHashChunk::Blob(data) => {
+            let tag = utils::sha384(format!("blob{}", data.len()).as_bytes());
+            utils::sha384(&[tag, utils::sha384(data)].concat())
+        }
+        DeepHashChunk::List(chunks) => {
+            let tag = utils::sha384(format!("list{}", chunks.len()).as_bytes());
+            chunks.iter().fold(tag, |acc, chunk| utils::sha384(&[acc, deep_hash(chunk)].concat()))
+        }
+    }
+}
+
+/// The ANS-104 data item signature message. Absent target, anchor or tags are
+/// hashed as empty blobs, tags being the raw avro bytes.
+pub fn data_item_signature_data(
+    sig_type_id: u64,
+    owner: &[u8],
+    target: &[u8],
+    anchor: &[u8],
+    tags: &[u8],
+    data: &[u8],
+) -> [u8; 48] {
+    let sig_type = sig_type_id.to_string();
+
+    deep_hash(&DeepHashChunk::List(vec![
+        DeepHashChunk::Blob(b"dataitem"),
+        DeepHashChunk::Blob(b"1"),
+        DeepHashChunk::Blob(sig_type.as_bytes()),
+        DeepHashChunk::Blob(owner),
+        DeepHashChunk::Blob(target),
+        DeepHashChunk::Blob(anchor),
+        DeepHashChunk::Blob(tags),
+        DeepHashChunk::Blob(data),
+    ]))
+}
+
+/// Decoded fields of a format 2 transaction, as signed by its owner.
+/// Binary fields are raw bytes, while quantity, reward and data size are the
+/// decimal strings found in the transaction json.
+pub struct TransactionFields<'a> {
+    pub owner: &'a [u8],
+    pub target: &'a [u8],
+    pub quantity: &'a str,
+    pub reward: &'a str,
+    pub last_tx: &'a [u8],
+    pub tags: &'a [Tag<Vec<u8>>],
+    pub data_size: &'a str,
+    pub data_root: &'a [u8],
+}
+
+/// The format 2 transaction signature message.
+pub fn transaction_signature_data(tx: &TransactionFields) -> [u8; 48] {
+    let tags = tx
+        .tags
+        .iter()
+        .map(|tag| {
+            DeepHashChunk::List(vec![
+                DeepHashChunk::Blob(&tag.name),
+                DeepHashChunk::Blob(&tag.value),
+            ])
+        })
+        .collect();
+
+    deep_hash(&DeepHashChunk::List(vec![
+        DeepHashChunk::Blob(b"2"),
+        DeepHashChunk::Blob(tx.owner),
+        DeepHashChunk::Blob(tx.target),
+        DeepHashChunk::Blob(tx.quantity.as_bytes()),
+        DeepHashChunk::Blob(tx.reward.as_bytes()),
+        DeepHashChunk::Blob(tx.last_tx),
+        DeepHashChunk::List(tags),
+        DeepHashChunk::Blob(tx.data_size.as_bytes()),
+        DeepHashChunk::Blob(tx.data_root),
+    ]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
+    use base64::Engine;
+
+    #[test]
+    fn test_deep_hash() {
+        let blob = deep_hash(&DeepHashChunk::Blob(b"hello"));
+        assert_eq!(
+            base64.encode(blob),
+            "M6skB6bDKMC8G75ZcfSa9cGQiYX4PD0r2JqeIh3YsGjcYc6Wi6P5qxLVNhujlEOC"
+        );
+
+        let list = deep_hash(&DeepHashChunk::List(vec![
+            DeepHashChunk::Blob(b"a"),
+            DeepHashChunk::List(vec![DeepHashChunk::Blob(b"b"), DeepHashChunk::Blob(b"")]),
+            DeepHashChunk::List(vec![]),
+        ]));
+        assert_eq!(
+            base64.encode(list),
+            "AzcZFQFhY_PjTEMmvpchgNMfEVfTnVNmFFk-imywsNJ0yiNrMw6-0ny54ur6JlcR"
+        );
+    }
+
+    #[test]
+    fn test_transaction_signature_data() {
+        let tags = [Tag {
+            name: b"name".to_vec(),
+            value: b"value".to_vec(),
+        }];
+
+        let message = transaction_signature_data(&TransactionFields {
+            owner: b"owner",
+            target: b"",
+            quantity: "0",
+            reward: "100",
+            last_tx: b"anchor",
+            tags: &tags,
+            data_size: "5",
+            data_root: b"root",
+        });
+        assert_eq!(
+            base64.encode(message),
+            "BxUxDSSQtk3BwA6TXPv-Kgwr1eb9zCShSlgue2T7f2hTlPnRS0BEO_fXtKiSjRFO"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use crate::bundle::{BundleItem, Error};
+use crate::utils::{file, mime};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// Extension used when neither the tags nor the data reveal the type.
+const DEFAULT_EXTENSION: &str = "bin";
+
+/// Links an extracted item to the file holding its data.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub id: String,
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    pub size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+}
+
+impl BundleItem {
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn content_type(&self) -> Option<String> {
+        self.tags.get("Content-Type")
+    }
+
+    /// File extension from the `Content-Type` tag, falling back to sniffing
+    /// the data.
+    pub fn extension(&self, data: &[u8]) -> &'static str {
+        self.content_type()
+            .and_then(|content_type| mime::extension_for_content_type(&content_type))
+            .or_else(|| mime::sniff_extension(data))
+            .unwrap_or(DEFAULT_EXTENSION)
+    }
+}
+
+/// Writes the data of items to `<dir>/<item_id>.<ext>` as they come, and the
+/// manifest of the written files once finished.
+/// Files go to `<dir>.partial/` and only move to the directory in `finish`, so
+/// the directory never holds an unfinished extraction. The partial directory
+/// is removed when an unfinished extractor is dropped.
+pub struct Extractor {
+    dir: PathBuf,
+    partial: PathBuf,
+    manifest: Vec<ManifestEntry>,
+}
+
+impl Extractor {
+    pub fn new(dir: &Path) -> Result<Self, Error> {
+        let partial = file::partial_path(dir);
+
+        // Left over by a run that did not get to clean up.
+        if partial.exists() {
+            std::fs::remove_dir_all(&partial)?;
+        }
+        std::fs::create_dir_all(&partial)?;
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            partial,
+            manifest: vec![],
+        })
+    }
+
+    /// Writes the data of the item and of its nested items.
+    pub fn extract(&mut self, item: &BundleItem) -> Result<(), Error> {
+        let data = item.data();
+        let file = format!("{}.{}", item.id, item.extension(data));
+        std::fs::write(self.partial.join(&file), data)?;
+
+        self.manifest.push(ManifestEntry {
+            id: item.id.clone(),
+            file,
+            content_type: item.content_type(),
+            size: data.len(),
+            parent_id: item.parent_id.clone(),
+        });
+
+        item.items.iter().try_for_each(|item| self.extract(item))
+    }
+
+    /// Saves the manifest and moves the files to the directory. Files of an
+    /// earlier extraction of the same bundle, such as a single item, are kept
+    /// and listed in the manifest along with the new ones.
+    pub fn finish(mut self) -> Result<Vec<ManifestEntry>, Error> {
+        let written = std::mem::take(&mut self.manifest);
+
+        if !self.dir.exists() {
+            file::save_serde_json(self.partial.join(MANIFEST_FILENAME), &written)?;
+            std::fs::rename(&self.partial, &self.dir)?;
+            return Ok(written);
+        }
+
+        for entry in &written {
+            std::fs::rename(self.partial.join(&entry.file), self.dir.join(&entry.file))?;
+        }
+
+        let mut manifest = read_manifest(&self.dir)?;
+        manifest.retain(|entry| !written.iter().any(|w| w.id == entry.id));
+        manifest.extend(written.iter().cloned());
+        file::save_serde_json(self.dir.join(MANIFEST_FILENAME), &manifest)?;
+
+        Ok(written)
+    }
+}
+
+/// Reads the manifest of an extraction directory, empty when there is none.
+fn read_manifest(dir: &Path) -> Result<Vec<ManifestEntry>, Error> {
+    match std::fs::read(dir.join(MANIFEST_FILENAME)) {
+        Ok(json) => Ok(serde_json::from_slice(&json)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e.into()),
+    }
+}
+
+impl Drop for Extractor {
+    fn drop(&mut self) {
+        // Already renamed when the extractor finished.
+        let _ = std::fs::remove_dir_all(&self.partial);
+    }
+}
+
+/// Writes the data of every item, nested ones included, to
+/// `<dir>/<item_id>.<ext>`, along with a manifest of the written files.
+pub fn extract_items(items: &[BundleItem], dir: &Path) -> Result<Vec<ManifestEntry>, Error> {
+    let mut extractor = Extractor::new(dir)?;
+    items.iter().try_for_each(|item| extractor.extract(item))?;
+    extractor.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::builder::{DataItemBuilder, Signer};
+    use crate::client::TAGS_AVRO_SCHEMA;
+    use apache_avro::Schema;
+
+    #[test]
+    fn test_extract_items() {
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let signer = Signer::from_ed25519_bytes(&[7; 32]).unwrap();
+
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        let items = vec![
+            DataItemBuilder::new(b"{}".to_vec())
+                .tag("Content-Type", "application/json; charset=utf-8")
+                .sign(&signer, &schema)
+                .unwrap(),
+            DataItemBuilder::new(png.clone()).sign(&signer, &schema).unwrap(),
+            DataItemBuilder::new(b"\0\x01".to_vec()).sign(&signer, &schema).unwrap(),
+        ];
+
+        let dir = std::env::temp_dir().join(format!("axer-extract-{}", std::process::id()));
+        let manifest = extract_items(&items, &dir).unwrap();
+
+        let files: Vec<&str> = manifest.iter().map(|entry| entry.file.as_str()).collect();
+        assert_eq!(
+            files,
+            [
+                format!("{}.json", items[0].id),
+                format!("{}.png", items[1].id),
+                format!("{}.bin", items[2].id),
+            ]
+        );
+        assert_eq!(manifest[0].content_type.as_deref(), Some("application/json; charset=utf-8"));
+        assert_eq!(std::fs::read(dir.join(&manifest[1].file)).unwrap(), png);
+        assert!(dir.join(MANIFEST_FILENAME).exists());
+
+        // Unfinished extractions leave neither the directory nor the partial one.
+        std::fs::remove_dir_all(&dir).unwrap();
+        let mut extractor = Extractor::new(&dir).unwrap();
+        extractor.extract(&items[0]).unwrap();
+        drop(extractor);
+        assert!(!dir.exists());
+        assert!(!dir.with_extension("partial").exists());
+
+        // Later extractions add to the earlier ones.
+        extract_items(&items[..2], &dir).unwrap();
+        let manifest = extract_items(&items[1..], &dir).unwrap();
+        assert_eq!(manifest.len(), 2);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 4);
+        assert_eq!(read_manifest(&dir).unwrap().len(), 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
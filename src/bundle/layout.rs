@@ -0,0 +1,158 @@
+use crate::bundle::ItemHeader;
+use serde::Serialize;
+use std::fmt::Display;
+
+/// Half-open byte range, `start..end`.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ByteRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl ByteRange {
+    /// The range of `length` bytes ending at `end`.
+    pub fn ending_at(end: usize, length: usize) -> Self {
+        Self {
+            start: end - length,
+            end,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    fn shift(self, offset: usize) -> Self {
+        Self {
+            start: self.start + offset,
+            end: self.end + offset,
+        }
+    }
+}
+
+impl Display for ByteRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+/// Ranges of the fields of a data item, relative to the item start.
+pub struct ItemFieldRanges {
+    pub signature: ByteRange,
+    pub owner: ByteRange,
+    pub target: Option<ByteRange>,
+    pub anchor: Option<ByteRange>,
+    pub tags: ByteRange,
+    pub data: ByteRange,
+}
+
+/// Where an item and its fields live in the raw bundle.
+/// Offsets are relative to the bundle the item belongs to, so for nested
+/// items they start at the data of the enclosing item.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ItemLayout {
+    pub index: usize,
+    pub header: ByteRange,
+    pub item: ByteRange,
+    pub signature: ByteRange,
+    pub owner: ByteRange,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<ByteRange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<ByteRange>,
+    pub tags: ByteRange,
+    pub data: ByteRange,
+}
+
+impl ItemLayout {
+    pub fn new(index: usize, header: &ItemHeader, offset: usize, fields: &ItemFieldRanges) -> Self {
+        let header_start = 32 + index * 64;
+
+        Self {
+            index,
+            header: ByteRange {
+                start: header_start,
+                end: header_start + 64,
+            },
+            item: ByteRange {
+                start: offset,
+                end: offset + header.length,
+            },
+            signature: fields.signature.shift(offset),
+            owner: fields.owner.shift(offset),
+            target: fields.target.map(|target| target.shift(offset)),
+            anchor: fields.anchor.map(|anchor| anchor.shift(offset)),
+            tags: fields.tags.shift(offset),
+            data: fields.data.shift(offset),
+        }
+    }
+}
+
+impl Display for ItemLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let optional = |range: Option<ByteRange>| range.map_or("-".to_string(), |r| r.to_string());
+
+        write!(
+            f,
+            "#{} header {} item {} signature {} owner {} target {} anchor {} tags {} data {}",
+            self.index,
+            self.header,
+            self.item,
+            self.signature,
+            self.owner,
+            optional(self.target),
+            optional(self.anchor),
+            self.tags,
+            self.data
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bundle::{Bundle, ParseOptions};
+    use crate::client::TAGS_AVRO_SCHEMA;
+    use apache_avro::Schema;
+    use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
+    use base64::Engine;
+
+    const TEST_BUNDLE: &str = "res/test_bundle";
+
+    #[test]
+    fn test_item_layout() {
+        let test_bundle = std::fs::read(TEST_BUNDLE).unwrap();
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let options = ParseOptions {
+            layout: true,
+            ..Default::default()
+        };
+        let bundle = Bundle::with_options(&test_bundle, &schema, &options).unwrap();
+
+        let mut item_start = 32 + bundle.items.len() * 64;
+        for (index, item) in bundle.items.iter().enumerate() {
+            let layout = item.layout.as_ref().unwrap();
+            let header = &test_bundle[layout.header.start..layout.header.end];
+
+            assert_eq!(layout.index, index);
+            assert_eq!(base64.encode(&header[32..]), item.id);
+            assert_eq!(layout.item.start, item_start);
+            assert_eq!(layout.signature.start, item_start + 2);
+            assert_eq!(layout.data.end, layout.item.end);
+
+            let signature = &test_bundle[layout.signature.start..layout.signature.end];
+            assert_eq!(base64.encode(signature), item.signature);
+            let data = &test_bundle[layout.data.start..layout.data.end];
+            assert_eq!(data, item.data);
+
+            item_start = layout.item.end;
+        }
+        assert_eq!(item_start, test_bundle.len());
+
+        let bundle = Bundle::new(&test_bundle, &schema).unwrap();
+        assert!(bundle.items.iter().all(|item| item.layout.is_none()));
+    }
+}
@@ -0,0 +1,401 @@
+use crate::bundle::layout::ByteRange;
+use crate::utils::sha256;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// Arweave splits transaction data into chunks of this size, except for the
+/// last two which are rebalanced so that none is smaller than the minimum.
+pub const MAX_CHUNK_SIZE: usize = 256 * 1024;
+pub const MIN_CHUNK_SIZE: usize = 32 * 1024;
+
+const HASH_SIZE: usize = 32;
+const NOTE_SIZE: usize = 32;
+
+/// Ranges of the chunks Arweave splits data of the given size into.
+/// Data sized to a multiple of the chunk size ends with an empty chunk.
+pub fn chunk_ranges(size: usize) -> Vec<ByteRange> {
+    let mut ranges = vec![];
+    let mut start = 0;
+
+    while size - start >= MAX_CHUNK_SIZE {
+        let rest = size - start;
+        let mut chunk_size = MAX_CHUNK_SIZE;
+
+        let next_chunk_size = rest - MAX_CHUNK_SIZE;
+        if next_chunk_size > 0 && next_chunk_size < MIN_CHUNK_SIZE {
+            chunk_size = rest.div_ceil(2);
+        }
+
+        ranges.push(ByteRange {
+            start,
+            end: start + chunk_size,
+        });
+        start += chunk_size;
+    }
+
+    ranges.push(ByteRange {
+        start,
+        end: size,
+    });
+    ranges
+}
+
+/// Offsets are hashed as 32-byte big-endian notes.
+fn note(value: usize) -> [u8; NOTE_SIZE] {
+    let mut note = [0; NOTE_SIZE];
+    note[NOTE_SIZE - 8..].copy_from_slice(&(value as u64).to_be_bytes());
+    note
+}
+
+fn read_note(note: &[u8]) -> Option<usize> {
+    let (high, low) = note.split_at(NOTE_SIZE - 8);
+    if high.iter().any(|byte| *byte != 0) {
+        return None;
+    }
+
+    usize::try_from(u64::from_be_bytes(low.try_into().ok()?)).ok()
+}
+
+fn leaf_id(data_hash: &[u8], note: &[u8]) -> [u8; HASH_SIZE] {
+    sha256(&[sha256(data_hash), sha256(note)].concat())
+}
+
+fn branch_id(left_id: &[u8], right_id: &[u8], note: &[u8]) -> [u8; HASH_SIZE] {
+    sha256(&[sha256(left_id), sha256(right_id), sha256(note)].concat())
+}
+
+enum Node {
+    Leaf {
+        id: [u8; HASH_SIZE],
+        data_hash: [u8; HASH_SIZE],
+        max: usize,
+    },
+    Branch {
+        id: [u8; HASH_SIZE],
+        left: Box<Node>,
+        right: Box<Node>,
+        max: usize,
+    },
+}
+
+impl Node {
+    fn id(&self) -> &[u8; HASH_SIZE] {
+        match self {
+            Node::Leaf {
+                id,
+                ..
+            }
+            | Node::Branch {
+                id,
+                ..
+            } => id,
+        }
+    }
+
+    fn max(&self) -> usize {
+        match self {
+            Node::Leaf {
+                max,
+                ..
+            }
+            | Node::Branch {
+                max,
+                ..
+            } => *max,
+        }
+    }
+}
+
+/// Pairs nodes layer by layer, promoting an odd node out to the next layer.
+fn build_tree(ranges: &[ByteRange], hashes: &[[u8; HASH_SIZE]]) -> Node {
+    let mut nodes: Vec<Node> = ranges
+        .iter()
+        .zip(hashes)
+        .map(|(range, data_hash)| Node::Leaf {
+            id: leaf_id(data_hash, &note(range.end)),
+            data_hash: *data_hash,
+            max: range.end,
+        })
+        .collect();
+
+    while nodes.len() > 1 {
+        let mut layer = vec![];
+        let mut nodes_iter = nodes.into_iter();
+
+        while let Some(left) = nodes_iter.next() {
+            match nodes_iter.next() {
+                Some(right) => layer.push(Node::Branch {
+                    id: branch_id(left.id(), right.id(), &note(left.max())),
+                    max: right.max(),
+                    left: Box::new(left),
+                    right: Box::new(right),
+                }),
+                None => layer.push(left),
+            }
+        }
+
+        nodes = layer;
+    }
+
+    nodes.pop().expect("chunk ranges are never empty")
+}
+
+/// Computes the merkle data root incrementally, as the data is read.
+pub struct ChunkHasher {
+    ranges: Vec<ByteRange>,
+    hashes: Vec<[u8; HASH_SIZE]>,
+    current: Sha256,
+    hashed: usize,
+    size: usize,
+}
+
+impl ChunkHasher {
+    pub fn new(size: usize) -> Self {
+        Self {
+            ranges: chunk_ranges(size),
+            hashes: vec![],
+            current: Sha256::new(),
+            hashed: 0,
+            size,
+        }
+    }
+
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            let range = match self.ranges.get(self.hashes.len()) {
+                Some(range) => range,
+                None => {
+                    // More data than declared, finalize reports the mismatch.
+                    self.hashed += bytes.len();
+                    return;
+                }
+            };
+
+            let length = (range.end - self.hashed).min(bytes.len());
+            self.current.update(&bytes[..length]);
+            self.hashed += length;
+            bytes = &bytes[length..];
+
+            if self.hashed == range.end {
+                self.hashes.push(self.current.finalize_reset().into());
+            }
+        }
+    }
+
+    /// Returns the data root, or `None` if the data read does not match the
+    /// declared size.
+    pub fn finalize(mut self) -> Option<[u8; HASH_SIZE]> {
+        if self.hashed != self.size {
+            return None;
+        }
+
+        // Only empty chunks can be left, as no bytes were read for them.
+        while self.hashes.len() < self.ranges.len() {
+            self.hashes.push(sha256(&[]));
+        }
+
+        Some(*build_tree(&self.ranges, &self.hashes).id())
+    }
+}
+
+/// Async reader that computes the data root of everything read through it.
+pub struct ChunkHashingReader<R> {
+    reader: R,
+    hasher: ChunkHasher,
+}
+
+impl<R: AsyncRead + Unpin> ChunkHashingReader<R> {
+    pub fn new(reader: R, size: usize) -> Self {
+        Self {
+            reader,
+            hasher: ChunkHasher::new(size),
+        }
+    }
+
+    pub fn finalize(self) -> Option<[u8; HASH_SIZE]> {
+        self.hasher.finalize()
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for ChunkHashingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let poll = Pin::new(&mut self.reader).poll_read(cx, buf);
+
+        if let Poll::Ready(Ok(())) = poll {
+            self.hasher.update(&buf.filled()[filled..]);
+        }
+        poll
+    }
+}
+
+pub fn data_root(data: &[u8]) -> [u8; HASH_SIZE] {
+    let mut hasher = ChunkHasher::new(data.len());
+    hasher.update(data);
+    hasher.finalize().expect("hashed data matches its own size")
+}
+
+/// Merkle proofs of every chunk of the data, in chunk order, as served in the
+/// `data_path` of `/chunk` responses.
+pub fn generate_proofs(data: &[u8]) -> Vec<Vec<u8>> {
+    fn resolve(node: &Node, path: Vec<u8>, proofs: &mut Vec<Vec<u8>>) {
+        match node {
+            Node::Leaf {
+                data_hash,
+                max,
+                ..
+            } => proofs.push([path, data_hash.to_vec(), note(*max).to_vec()].concat()),
+            Node::Branch {
+                left,
+                right,
+                ..
+            } => {
+                let path =
+                    [path, left.id().to_vec(), right.id().to_vec(), note(left.max()).to_vec()]
+                        .concat();
+                resolve(left, path.clone(), proofs);
+                resolve(right, path, proofs);
+            }
+        }
+    }
+
+    let ranges = chunk_ranges(data.len());
+    let hashes: Vec<_> = ranges.iter().map(|range| sha256(&data[range.start..range.end])).collect();
+
+    let mut proofs = vec![];
+    resolve(&build_tree(&ranges, &hashes), vec![], &mut proofs);
+    proofs
+}
+
+/// A chunk proven to be part of the data under a root.
+#[derive(Debug, PartialEq)]
+pub struct ProvenChunk {
+    /// Range of the chunk within the data.
+    pub range: ByteRange,
+    /// SHA-256 of the chunk bytes.
+    pub data_hash: [u8; HASH_SIZE],
+}
+
+/// Walks a merkle proof from the root down to the chunk holding the `offset`
+/// byte of data of the given size, checking every node hash on the way.
+pub fn validate_path(root: &[u8], offset: usize, size: usize, path: &[u8]) -> Option<ProvenChunk> {
+    if offset >= size {
+        return None;
+    }
+
+    let mut id = root;
+    let mut path = path;
+    let mut range = ByteRange {
+        start: 0,
+        end: size,
+    };
+
+    while path.len() > HASH_SIZE + NOTE_SIZE {
+        if path.len() < 2 * HASH_SIZE + NOTE_SIZE {
+            return None;
+        }
+
+        let (left, rest) = path.split_at(HASH_SIZE);
+        let (right, rest) = rest.split_at(HASH_SIZE);
+        let (note, rest) = rest.split_at(NOTE_SIZE);
+
+        if branch_id(left, right, note) != id {
+            return None;
+        }
+
+        let split = read_note(note)?;
+        match offset < split {
+            true => {
+                id = left;
+                range.end = range.end.min(split);
+            }
+            false => {
+                id = right;
+                range.start = range.start.max(split);
+            }
+        }
+        path = rest;
+    }
+
+    if path.len() != HASH_SIZE + NOTE_SIZE {
+        return None;
+    }
+
+    let (data_hash, note) = path.split_at(HASH_SIZE);
+    match leaf_id(data_hash, note) == id && read_note(note) == Some(range.end) {
+        true => Some(ProvenChunk {
+            range,
+            data_hash: data_hash.try_into().ok()?,
+        }),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
+    use base64::Engine;
+
+    #[test]
+    fn test_chunk_ranges() {
+        let lengths = |size| chunk_ranges(size).iter().map(ByteRange::len).collect::<Vec<_>>();
+
+        assert_eq!(lengths(0), [0]);
+        assert_eq!(lengths(10), [10]);
+        assert_eq!(lengths(MAX_CHUNK_SIZE), [MAX_CHUNK_SIZE, 0]);
+        assert_eq!(lengths(MAX_CHUNK_SIZE + MIN_CHUNK_SIZE), [MAX_CHUNK_SIZE, MIN_CHUNK_SIZE]);
+
+        // A last chunk under the minimum is rebalanced with the one before it.
+        let size = 2 * MAX_CHUNK_SIZE + 1000;
+        let half = (MAX_CHUNK_SIZE + 1000) / 2;
+        assert_eq!(lengths(size), [MAX_CHUNK_SIZE, half, half]);
+    }
+
+    #[test]
+    fn test_data_root() {
+        // Roots computed with a Python port of arweave-js merkle.ts, over data of
+        // repeating 0..=255 bytes.
+        let data: Vec<u8> = (0..2 * MAX_CHUNK_SIZE + 1000).map(|i| i as u8).collect();
+        assert_eq!(
+            base64.encode(data_root(&data[..1000])),
+            "TVAqMGhF_2oRVnZiTbAWWPa-dpuW3fSC47ecH4PbofQ"
+        );
+        assert_eq!(base64.encode(data_root(&data)), "nNv87uDBFIgzGtMeMC2lrRc1HC9elKS6Dp5bpvpip4k");
+
+        let mut hasher = ChunkHasher::new(data.len());
+        data.chunks(7777).for_each(|chunk| hasher.update(chunk));
+        assert_eq!(hasher.finalize(), Some(data_root(&data)));
+
+        let mut hasher = ChunkHasher::new(data.len());
+        hasher.update(&data[1..]);
+        assert_eq!(hasher.finalize(), None);
+    }
+
+    #[test]
+    fn test_validate_path() {
+        let data: Vec<u8> = (0..3 * MAX_CHUNK_SIZE).map(|i| i as u8).collect();
+        let root = data_root(&data);
+        let proofs = generate_proofs(&data);
+        let ranges = chunk_ranges(data.len());
+        assert_eq!(proofs.len(), ranges.len());
+
+        for (range, proof) in ranges.iter().zip(&proofs).filter(|(range, _)| !range.is_empty()) {
+            let chunk = validate_path(&root, range.start, data.len(), proof).unwrap();
+            assert_eq!(chunk.range, *range);
+            assert_eq!(chunk.data_hash, sha256(&data[range.start..range.end]));
+        }
+
+        assert!(validate_path(&root, 0, data.len(), &proofs[1]).is_none());
+        assert!(validate_path(&[0; 32], 0, data.len(), &proofs[0]).is_none());
+        assert!(validate_path(&root, data.len(), data.len(), &proofs[0]).is_none());
+        assert!(validate_path(&root, 0, data.len(), &proofs[0][1..]).is_none());
+    }
+}
@@ -1,16 +1,30 @@
+use crate::bundle::cursor::ItemCursor;
+use crate::bundle::deep_hash::data_item_signature_data;
+use crate::bundle::layout::{ByteRange, ItemFieldRanges, ItemLayout};
 use crate::bundle::signatures::get_sig_types;
-use crate::bundle::tags::Base64Tags;
+use crate::bundle::tags::{Base64Tags, Tag, TagEncoding, TagValue};
+use crate::bundle::tx::{BUNDLE_FORMAT_SUPPORTED, BUNDLE_VERSION_SUPPORTED};
 use crate::utils;
+use crate::utils::U256;
 use apache_avro::Schema;
+use base64::display::Base64Display;
 use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
 use base64::Engine;
-use serde::Serialize;
+use serde::{Serialize, Serializer};
 use thiserror::Error;
 
+pub mod builder;
+mod cursor;
+pub mod deep_hash;
+pub mod extract;
+pub mod layout;
+pub mod merkle;
 pub mod network;
 pub mod signatures;
+pub mod stream;
 pub mod tags;
 pub mod tx;
+pub mod writer;
 
 #[derive(Debug, Error)]
 pub enum Error {
@@ -22,14 +36,17 @@ pub enum Error {
     ItemHeaderIncomplete,
     #[error("bundle data length is less than expected, item data incomplete")]
     ItemDataIncomplete,
-    #[error("item data length is less than 2")]
-    ItemDataLessThanMinimum,
     #[error("signature not supported: {sig_type_id:?}")]
     SignatureNotSupported {
         sig_type_id: u64,
     },
-    #[error("item data length does not include signature")]
-    ItemDataLessThanSignature,
+    #[error("item data incomplete, {field} at offset {offset} needs {needed} bytes but {available} are left")]
+    ItemFieldIncomplete {
+        field: &'static str,
+        offset: usize,
+        needed: usize,
+        available: usize,
+    },
     #[error("failed to parse tags: {0}")]
     TagsParseError(#[from] tags::Error),
     #[error("item id mismatch, expected {expected:?} but found {found:?}")]
@@ -39,63 +56,263 @@ pub enum Error {
     },
     #[error("failed to parse byte array")]
     BytesError(#[from] utils::Error),
+    #[error("bundle item count {count} exceeds the addressable size")]
+    ItemCountTooLarge {
+        count: U256,
+    },
+    #[error("item {id} length {length} exceeds the addressable size")]
+    ItemLengthTooLarge {
+        id: String,
+        length: U256,
+    },
+    #[error("failed to build item: {0}")]
+    BuilderError(#[from] builder::Error),
+    #[error("failed to decode item field: {0}")]
+    DecodeError(#[from] base64::DecodeError),
+    #[error("failed to read bundle: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("failed to read manifest: {0}")]
+    ManifestError(#[from] serde_json::Error),
+    #[error("failed to unpack nested bundle {id}: {source}")]
+    NestedBundleError {
+        id: String,
+        source: Box<Error>,
+    },
 }
 
-#[derive(Serialize, PartialEq)]
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Levels of nested bundles to unpack, 0 keeps them as plain items.
+    pub max_depth: u32,
+    /// Record failing items as diagnostics and continue with the next header
+    /// entry, instead of failing the whole bundle.
+    pub lenient: bool,
+    /// Record the byte ranges of every item and its fields.
+    pub layout: bool,
+}
+
+#[derive(Serialize)]
 pub struct Bundle {
     pub items: Vec<BundleItem>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub diagnostics: Vec<ItemDiagnostic>,
+}
+
+/// An item that failed to parse in lenient mode.
+#[derive(Serialize, Debug)]
+pub struct ItemDiagnostic {
+    /// Position of the item in the header table.
+    pub index: usize,
+    /// Item id as declared by the header table.
+    pub id: String,
+    /// Byte offset of the item within its bundle.
+    pub offset: usize,
+    /// Id of the enclosing item when the failure happened in a nested bundle.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(serialize_with = "serialize_display")]
+    pub error: Error,
+}
+
+fn serialize_display<S: Serializer>(error: &Error, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(error)
+}
+
+/// Writes bytes as base64 straight into the serializer, without an
+/// intermediate string.
+fn serialize_base64<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&Base64Display::new(bytes, &base64))
+}
+
+/// Writes tags as readable strings, non UTF-8 bytes fall back to base64.
+fn serialize_tags<S: Serializer>(tags: &Base64Tags, serializer: S) -> Result<S::Ok, S::Error> {
+    tags.decoded(TagEncoding::Base64).serialize(serializer)
 }
 
 impl Bundle {
     pub fn new(data: &[u8], tags_schema: &Schema) -> Result<Self, Error> {
+        Self::with_options(data, tags_schema, &ParseOptions::default())
+    }
+
+    pub fn with_options(
+        data: &[u8],
+        tags_schema: &Schema,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
         if data.len() < 32 {
             return Err(Error::BundleLessThanMinimum);
         }
 
-        // Even though we try to read 32 bytes into a u64, the function only supports 8 bytes
-        // and overflow is checked. In the future, this should support a u256, as the ANS-104
-        // allows it.
-        let items_len: u64 = utils::byte_array_to_u64(&data[..32])?;
+        let items_len = items_len(&data[..32])?;
+        let headers_end = headers_end(items_len)?;
 
-        if data.len() < (32 + items_len * 64) as usize {
+        if data.len() < headers_end {
             return Err(Error::BundleHeadersIncomplete);
         }
 
-        let mut items: Vec<BundleItem> = vec![];
-        let mut items_start = (32 + items_len * 64) as usize;
-        for i in 0..items_len {
-            let item_header_begin = (32 + i * 64) as usize;
+        let mut bundle = Self {
+            items: vec![],
+            diagnostics: vec![],
+        };
+
+        let mut items_start = headers_end;
+        for index in 0..items_len {
+            let item_header_begin = 32 + index * 64;
             let item_header_end = item_header_begin + 64;
 
-            if data.len() < item_header_end {
-                return Err(Error::ItemHeaderIncomplete);
+            let header_bytes: &[u8; 64] = data
+                .get(item_header_begin..item_header_end)
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or(Error::ItemHeaderIncomplete)?;
+            let header = match ItemHeader::from_bytes(header_bytes) {
+                Ok(header) => header,
+                Err(error) if options.lenient => {
+                    // Without the item length, the offsets of the items after it are unknown.
+                    bundle.diagnostics.push(ItemDiagnostic {
+                        index,
+                        id: base64.encode(&header_bytes[32..64]),
+                        offset: items_start,
+                        parent_id: None,
+                        error,
+                    });
+                    break;
+                }
+                Err(error) => return Err(error),
+            };
+
+            let item_start = items_start;
+            let item_end = items_start.checked_add(header.length);
+            items_start = item_end.unwrap_or(usize::MAX);
+
+            let result = match item_end {
+                Some(end) if end <= data.len() => {
+                    let item_data = &data[item_start..end];
+                    parse_item(item_data, index, &header, item_start, tags_schema, options)
+                }
+                _ => Err(Error::ItemDataIncomplete),
+            };
+
+            match result {
+                Ok((item, diagnostics)) => {
+                    bundle.items.push(item);
+                    bundle.diagnostics.extend(diagnostics);
+                }
+                Err(error) if options.lenient => bundle.diagnostics.push(ItemDiagnostic {
+                    index,
+                    id: header.id,
+                    offset: item_start,
+                    parent_id: None,
+                    error,
+                }),
+                Err(error) => return Err(error),
             }
+        }
 
-            let header = &data[item_header_begin..item_header_end];
-            let item_length = utils::byte_array_to_u64(&header[..32])? as usize;
-            let item_id = base64.encode(&header[32..64]);
+        Ok(bundle)
+    }
 
-            if data.len() < items_start + item_length {
-                return Err(Error::ItemDataIncomplete);
-            }
+    /// Drops every item, nested ones included, whose signature could not be
+    /// verified and returns how many were dropped.
+    pub fn retain_verified(&mut self) -> usize {
+        retain_verified(&mut self.items)
+    }
 
-            let item_data = &data[items_start..items_start + item_length];
-            let item = BundleItem::new(item_data, tags_schema)?;
-            if item.id != item_id {
-                return Err(Error::IdMismatch {
-                    expected: item_id,
-                    found: item.id,
-                });
-            }
+    /// Fills `decoded_tags` on every item, nested ones included, for output.
+    pub fn decode_tags(&mut self, fallback: TagEncoding) {
+        self.items.iter_mut().for_each(|item| item.decode_tags(fallback));
+    }
+}
+
+/// An entry of the bundle header table.
+pub struct ItemHeader {
+    pub length: usize,
+    pub id: String,
+}
+
+impl ItemHeader {
+    /// Parses a 64-byte header entry, the item length followed by its id.
+    pub fn from_bytes(header: &[u8; 64]) -> Result<Self, Error> {
+        let id = base64.encode(&header[32..64]);
+        let length = U256::from_le_slice(&header[..32])?;
+
+        match length.to_usize() {
+            Ok(length) => Ok(Self {
+                length,
+                id,
+            }),
+            Err(_) => Err(Error::ItemLengthTooLarge {
+                id,
+                length,
+            }),
+        }
+    }
+}
+
+/// Reads the u256 item count from the first 32 bytes of a bundle.
+pub fn items_len(data: &[u8]) -> Result<usize, Error> {
+    let count = U256::from_le_slice(data)?;
+    count.to_usize().map_err(|_| Error::ItemCountTooLarge {
+        count,
+    })
+}
+
+/// Offset where the header table ends and the item data begins.
+pub fn headers_end(items_len: usize) -> Result<usize, Error> {
+    items_len.checked_mul(64).and_then(|len| len.checked_add(32)).ok_or(Error::ItemCountTooLarge {
+        count: U256::from(items_len),
+    })
+}
 
-            items.push(item);
-            items_start += item_length;
+/// Parses the item described by a header entry, checking its id and
+/// unpacking it if it is a nested bundle. Returns the diagnostics of the
+/// nested bundle, if any.
+pub fn parse_item(
+    item_data: &[u8],
+    index: usize,
+    header: &ItemHeader,
+    offset: usize,
+    tags_schema: &Schema,
+    options: &ParseOptions,
+) -> Result<(BundleItem, Vec<ItemDiagnostic>), Error> {
+    let (mut item, fields) = BundleItem::parse(item_data, tags_schema)?;
+    if item.id != header.id {
+        return Err(Error::IdMismatch {
+            expected: header.id.clone(),
+            found: item.id,
+        });
+    }
+
+    let mut diagnostics = vec![];
+    if options.max_depth > 0 && item.is_bundle() {
+        match item.unpack(&item_data[fields.data.start..], tags_schema, options) {
+            Ok(nested) => diagnostics = nested,
+            // The item itself is valid, only its data failed to unpack, so it
+            // is kept as a plain item. The parent is set by the enclosing
+            // bundle, if nested.
+            Err(error) if options.lenient => diagnostics.push(ItemDiagnostic {
+                index,
+                id: item.id.clone(),
+                offset,
+                parent_id: None,
+                error,
+            }),
+            Err(error) => return Err(error),
         }
+    }
 
-        Ok(Self {
-            items,
-        })
+    if options.layout {
+        item.layout = Some(ItemLayout::new(index, header, offset, &fields));
     }
+
+    Ok((item, diagnostics))
+}
+
+fn retain_verified(items: &mut Vec<BundleItem>) -> usize {
+    let total = items.len();
+    items.retain(|item| item.verified);
+
+    let nested: usize = items.iter_mut().map(|item| retain_verified(&mut item.items)).sum();
+    total - items.len() + nested
 }
 
 #[derive(Serialize, PartialEq)]
@@ -104,113 +321,164 @@ pub struct BundleItem {
     owner: String,
     target: String,
     anchor: String,
+    #[serde(serialize_with = "serialize_tags")]
     tags: Base64Tags,
-    data: String,
+    /// Avro encoded tags as read, kept for writing the item back, since the
+    /// same tags may be encoded in more than one way.
+    #[serde(skip)]
+    tag_bytes: Vec<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decoded_tags: Option<Vec<Tag<TagValue>>>,
+    #[serde(serialize_with = "serialize_base64")]
+    data: Vec<u8>,
     signature: String,
     signature_type: u16,
+    pub verified: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<BundleItem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<ItemLayout>,
 }
 
 impl BundleItem {
     pub fn new(data: &[u8], tags_schema: &Schema) -> Result<Self, Error> {
-        if data.len() < 2 {
-            return Err(Error::ItemDataLessThanMinimum);
-        }
-
-        let sig_types = get_sig_types();
-
-        let sig_type_id = utils::byte_array_to_u64(&data[..2])?;
-        if !sig_types.contains_key(&sig_type_id) {
-            return Err(Error::SignatureNotSupported {
-                sig_type_id,
-            });
-        }
-
-        let sig_type = sig_types.get(&sig_type_id).unwrap();
-        let sig_length = sig_type.sig_length as usize;
-        let pub_length = sig_type.pub_length as usize;
-
-        if data.len() < 2 + sig_length {
-            return Err(Error::ItemDataIncomplete);
-        }
-
-        let sig_bytes = &data[2..2 + sig_length];
-        let signature = base64.encode(sig_bytes);
-        let id_hash = utils::sha256(sig_bytes);
-        let id = base64.encode(id_hash);
-
-        if data.len() < sig_length + pub_length + 2 {
-            return Err(Error::ItemDataLessThanSignature);
-        }
-
-        let owner = base64.encode(&data[2 + sig_length..2 + sig_length + pub_length]);
-
-        let mut target = String::new();
-        let mut anchor = String::new();
-        let pos = 2 + sig_length + pub_length;
+        Ok(Self::parse(data, tags_schema)?.0)
+    }
 
-        let mut anchor_byte = pos + 1;
-        let mut tags_start = pos + 2;
+    /// Drops the nested items whose signature could not be verified and
+    /// returns how many were dropped.
+    pub fn retain_verified(&mut self) -> usize {
+        retain_verified(&mut self.items)
+    }
 
-        if data.len() < pos {
-            return Err(Error::ItemDataIncomplete);
-        }
+    /// Fills `decoded_tags` on the item and its nested items, for output.
+    pub fn decode_tags(&mut self, fallback: TagEncoding) {
+        self.decoded_tags = Some(self.tags.decoded(fallback));
+        self.items.iter_mut().for_each(|item| item.decode_tags(fallback));
+    }
 
-        let target_available = data[pos] == 1;
-        if target_available {
-            tags_start += 32;
-            anchor_byte += 32;
+    /// Whether the item data is itself a bundle, as marked by its tags.
+    pub fn is_bundle(&self) -> bool {
+        self.tags.get("Bundle-Format").is_some_and(|f| f == BUNDLE_FORMAT_SUPPORTED)
+            && self.tags.get("Bundle-Version").is_some_and(|v| v == BUNDLE_VERSION_SUPPORTED)
+    }
 
-            if data.len() < pos + 1 + 32 {
-                return Err(Error::ItemDataIncomplete);
+    fn unpack(
+        &mut self,
+        data: &[u8],
+        tags_schema: &Schema,
+        options: &ParseOptions,
+    ) -> Result<Vec<ItemDiagnostic>, Error> {
+        let options = ParseOptions {
+            max_depth: options.max_depth - 1,
+            ..options.clone()
+        };
+
+        let bundle = Bundle::with_options(data, tags_schema, &options).map_err(|e| {
+            Error::NestedBundleError {
+                id: self.id.clone(),
+                source: Box::new(e),
             }
+        })?;
 
-            target = base64.encode(&data[pos + 1..pos + 1 + 32]);
+        self.items = bundle.items;
+        for item in self.items.iter_mut() {
+            item.parent_id = Some(self.id.clone());
         }
 
-        let anchor_available = data[anchor_byte] == 1;
-        if anchor_available {
-            tags_start += 32;
-
-            if data.len() < anchor_byte + 1 + 32 {
-                return Err(Error::ItemDataIncomplete);
-            }
-
-            anchor = base64.encode(&data[anchor_byte + 1..anchor_byte + 1 + 32]);
+        let mut diagnostics = bundle.diagnostics;
+        for diagnostic in diagnostics.iter_mut() {
+            diagnostic.parent_id.get_or_insert_with(|| self.id.clone());
         }
 
-        let tag_count = utils::byte_array_to_u64(&data[tags_start..tags_start + 8])?;
-        let mut tag_bytes_length: usize = 0;
-        let tag_bytes: Vec<u8>;
+        Ok(diagnostics)
+    }
 
-        let mut tags = Base64Tags(vec![]);
+    /// Parses an item, also returning the byte ranges of its fields.
+    fn parse(data: &[u8], tags_schema: &Schema) -> Result<(Self, ItemFieldRanges), Error> {
+        let mut cursor = ItemCursor::new(data);
 
-        if tag_count > 0 {
-            if data.len() < tags_start + 16 {
-                return Err(Error::ItemDataIncomplete);
-            }
-
-            tag_bytes_length =
-                utils::byte_array_to_u64(&data[tags_start + 8..tags_start + 16])? as usize;
-            if data.len() < tags_start + 16 + tag_bytes_length {
-                return Err(Error::ItemDataIncomplete);
+        let sig_type_id = cursor.read_u16("signature type")? as u64;
+        let sig_types = get_sig_types();
+        let sig_type = match sig_types.get(&sig_type_id) {
+            Some(sig_type) => sig_type,
+            None => {
+                return Err(Error::SignatureNotSupported {
+                    sig_type_id,
+                })
             }
+        };
 
-            tag_bytes = data[tags_start + 16..tags_start + 16 + tag_bytes_length].to_vec();
-            tags = Base64Tags::from_avro(tags_schema, tag_bytes)?;
-        }
-
-        let data = base64.encode(&data[tags_start + 16 + tag_bytes_length..]);
+        let sig_bytes = cursor.read("signature", sig_type.sig_length as usize)?;
+        let signature_range = ByteRange::ending_at(cursor.position(), sig_bytes.len());
+        let signature = base64.encode(sig_bytes);
+        let id_hash = utils::sha256(sig_bytes);
+        let id = base64.encode(id_hash);
 
-        Ok(Self {
+        let owner_bytes = cursor.read("owner", sig_type.pub_length as usize)?;
+        let owner_range = ByteRange::ending_at(cursor.position(), owner_bytes.len());
+        let owner = base64.encode(owner_bytes);
+
+        let target_bytes = cursor.read_optional("target", 32)?;
+        let target_range = (!target_bytes.is_empty())
+            .then(|| ByteRange::ending_at(cursor.position(), target_bytes.len()));
+
+        let anchor_bytes = cursor.read_optional("anchor", 32)?;
+        let anchor_range = (!anchor_bytes.is_empty())
+            .then(|| ByteRange::ending_at(cursor.position(), anchor_bytes.len()));
+
+        let tag_count = cursor.read_u64("tag count")?;
+        let tag_bytes_length = cursor.read_u64("tag bytes length")?;
+
+        // The declared length always applies, even without tags, so the bytes it
+        // covers are checked against the tag count rather than read as data.
+        let tag_bytes =
+            cursor.read("tags", usize::try_from(tag_bytes_length).unwrap_or(usize::MAX))?;
+        let tags_range = ByteRange::ending_at(cursor.position(), tag_bytes.len());
+        let tags = Base64Tags::from_item(tags_schema, tag_count, tag_bytes)?;
+
+        let data_bytes = cursor.rest();
+        let data_range = ByteRange::ending_at(cursor.position(), data_bytes.len());
+
+        let message = data_item_signature_data(
+            sig_type_id,
+            owner_bytes,
+            target_bytes,
+            anchor_bytes,
+            tag_bytes,
+            data_bytes,
+        );
+        let verified = (sig_type.verify)(owner_bytes, &message, sig_bytes);
+
+        let item = Self {
             id,
             owner,
-            target,
-            anchor,
+            target: base64.encode(target_bytes),
+            anchor: base64.encode(anchor_bytes),
             tags,
-            data,
+            tag_bytes: tag_bytes.to_vec(),
+            decoded_tags: None,
+            data: data_bytes.to_vec(),
             signature,
             signature_type: sig_type_id as u16,
-        })
+            verified,
+            parent_id: None,
+            items: vec![],
+            layout: None,
+        };
+
+        let fields = ItemFieldRanges {
+            signature: signature_range,
+            owner: owner_range,
+            target: target_range,
+            anchor: anchor_range,
+            tags: tags_range,
+            data: data_range,
+        };
+
+        Ok((item, fields))
     }
 }
 
@@ -218,6 +486,7 @@ impl BundleItem {
 mod tests {
     use super::*;
     use crate::client::TAGS_AVRO_SCHEMA;
+    use ed25519_dalek::Signer;
     use std::path::Path;
 
     const TEST_BUNDLE: &str = "res/test_bundle";
@@ -242,4 +511,232 @@ mod tests {
 
         assert_eq!(json, test_bundle_json);
     }
+
+    #[test]
+    fn test_bundle_item_tampered() {
+        let mut test_bundle = std::fs::read(TEST_BUNDLE).unwrap();
+        *test_bundle.last_mut().unwrap() ^= 1;
+
+        let bundle =
+            Bundle::new(test_bundle.as_slice(), &Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap())
+                .unwrap();
+
+        assert!(bundle.items[0].verified);
+        assert!(!bundle.items[1].verified);
+    }
+
+    #[test]
+    fn test_bundle_u256_fields() {
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+
+        let mut count = [0; 32];
+        count[31] = 1;
+        assert!(matches!(Bundle::new(&count, &schema), Err(Error::ItemCountTooLarge { .. })));
+
+        let mut bundle = [0; 96];
+        bundle[0] = 1;
+        bundle[32 + 20] = 1;
+        match Bundle::new(&bundle, &schema) {
+            Err(Error::ItemLengthTooLarge {
+                length,
+                ..
+            }) => assert_eq!(length.to_le_bytes()[20], 1),
+            _ => panic!("expected item length error"),
+        }
+
+        let mut bundle = [0; 96];
+        bundle[0] = 1;
+        bundle[32..40].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(Bundle::new(&bundle, &schema), Err(Error::ItemDataIncomplete)));
+    }
+
+    #[test]
+    fn test_bundle_item_truncated() {
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let item = ed25519_item(&[("Content-Type", "text/plain")], b"data");
+
+        for length in 0..item.len() - 4 {
+            let result = BundleItem::new(&item[..length], &schema);
+            assert!(matches!(result, Err(Error::ItemFieldIncomplete { .. })), "length {length}");
+        }
+
+        match BundleItem::new(&item[..70], &schema) {
+            Err(Error::ItemFieldIncomplete {
+                field,
+                offset,
+                needed,
+                available,
+            }) => assert_eq!((field, offset, needed, available), ("owner", 66, 32, 4)),
+            _ => panic!("expected incomplete owner"),
+        }
+
+        // A target flag at the very end of the item.
+        let mut flagged = item[..98].to_vec();
+        flagged.push(1);
+        assert!(matches!(
+            BundleItem::new(&flagged, &schema),
+            Err(Error::ItemFieldIncomplete {
+                field: "target",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_bundle_lenient() {
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let first = ed25519_item(&[], b"first");
+        let mut forged = ed25519_item(&[], b"forged");
+        forged[0] = 99;
+        let last = ed25519_item(&[], b"last");
+
+        let data = bundle_of(&[first.clone(), forged, last]);
+        assert!(matches!(
+            Bundle::new(&data, &schema),
+            Err(Error::SignatureNotSupported {
+                sig_type_id: 99
+            })
+        ));
+
+        let options = ParseOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let bundle = Bundle::with_options(&data, &schema, &options).unwrap();
+        assert_eq!(bundle.items.len(), 2);
+        assert_eq!(bundle.items[1].data, b"last");
+
+        assert_eq!(bundle.diagnostics.len(), 1);
+        let diagnostic = &bundle.diagnostics[0];
+        assert_eq!(diagnostic.index, 1);
+        assert_eq!(diagnostic.offset, 32 + 3 * 64 + first.len());
+        assert_eq!(
+            diagnostic.id,
+            base64.encode(utils::sha256(&data[diagnostic.offset + 2..][..64]))
+        );
+    }
+
+    fn ed25519_item(tags: &[(&str, &str)], data: &[u8]) -> Vec<u8> {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let owner = key.verifying_key().to_bytes();
+
+        let tags = Base64Tags(
+            tags.iter()
+                .map(|(name, value)| tags::Tag {
+                    name: tags::Base64::encode(name.as_bytes()),
+                    value: tags::Base64::encode(value.as_bytes()),
+                })
+                .collect(),
+        );
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let tag_bytes = tags.to_avro(&schema).unwrap();
+
+        let message = data_item_signature_data(2, &owner, &[], &[], &tag_bytes, data);
+        let signature = key.sign(&message).to_bytes();
+
+        writer::encode_item(&writer::ItemFields {
+            signature_type: 2,
+            signature: &signature,
+            owner: &owner,
+            target: &[],
+            anchor: &[],
+            tags_count: tags.0.len(),
+            tags: &tag_bytes,
+            data,
+        })
+    }
+
+    fn bundle_of(items: &[Vec<u8>]) -> Vec<u8> {
+        let mut bundle = [0; 32].to_vec();
+        bundle[..8].copy_from_slice(&(items.len() as u64).to_le_bytes());
+
+        for item in items {
+            let mut length = [0; 32];
+            length[..8].copy_from_slice(&(item.len() as u64).to_le_bytes());
+            bundle.extend_from_slice(&length);
+            bundle.extend_from_slice(&utils::sha256(&item[2..66]));
+        }
+
+        items.iter().for_each(|item| bundle.extend_from_slice(item));
+        bundle
+    }
+
+    #[test]
+    fn test_bundle_nested() {
+        let inner = bundle_of(&[ed25519_item(&[("Content-Type", "text/plain")], b"inner")]);
+        let outer = bundle_of(&[ed25519_item(
+            &[("Bundle-Format", "binary"), ("Bundle-Version", "2.0.0")],
+            &inner,
+        )]);
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+
+        let bundle = Bundle::new(&outer, &schema).unwrap();
+        assert!(bundle.items[0].is_bundle());
+        assert!(bundle.items[0].items.is_empty());
+
+        let options = ParseOptions {
+            max_depth: 1,
+            ..Default::default()
+        };
+        let bundle = Bundle::with_options(&outer, &schema, &options).unwrap();
+        let parent = &bundle.items[0];
+
+        assert_eq!(parent.items.len(), 1);
+        assert_eq!(parent.items[0].parent_id, Some(parent.id.clone()));
+        assert_eq!(parent.items[0].data, b"inner");
+        assert!(parent.items[0].verified);
+    }
+
+    #[test]
+    fn test_bundle_nested_lenient() {
+        let outer = bundle_of(&[ed25519_item(
+            &[("Bundle-Format", "binary"), ("Bundle-Version", "2.0.0")],
+            b"not a bundle",
+        )]);
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+
+        let options = ParseOptions {
+            max_depth: 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Bundle::with_options(&outer, &schema, &options),
+            Err(Error::NestedBundleError { .. })
+        ));
+
+        let options = ParseOptions {
+            lenient: true,
+            ..options
+        };
+        let bundle = Bundle::with_options(&outer, &schema, &options).unwrap();
+        assert_eq!(bundle.items.len(), 1);
+        assert!(bundle.items[0].items.is_empty());
+
+        assert_eq!(bundle.diagnostics.len(), 1);
+        let diagnostic = &bundle.diagnostics[0];
+        assert_eq!(diagnostic.id, bundle.items[0].id);
+        assert_eq!(diagnostic.parent_id, None);
+        assert!(matches!(
+            &diagnostic.error,
+            Error::NestedBundleError { source, .. } if matches!(**source, Error::BundleLessThanMinimum)
+        ));
+
+        // One level down, the parent is the enclosing bundle item.
+        let root = bundle_of(&[ed25519_item(
+            &[("Bundle-Format", "binary"), ("Bundle-Version", "2.0.0")],
+            &outer,
+        )]);
+        let options = ParseOptions {
+            max_depth: 2,
+            ..options
+        };
+        let bundle = Bundle::with_options(&root, &schema, &options).unwrap();
+        let parent = &bundle.items[0];
+        assert_eq!(parent.items.len(), 1);
+
+        assert_eq!(bundle.diagnostics.len(), 1);
+        let diagnostic = &bundle.diagnostics[0];
+        assert_eq!(diagnostic.id, parent.items[0].id);
+        assert_eq!(diagnostic.parent_id, Some(parent.id.clone()));
+    }
 }
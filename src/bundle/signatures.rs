@@ -1,9 +1,27 @@
+use crate::utils;
+use ed25519_dalek::Verifier;
+use k256::ecdsa::signature::hazmat::PrehashVerifier;
+use rsa::traits::PublicKeyParts;
+use rsa::{BigUint, Pss, RsaPublicKey};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
 use std::collections::HashMap;
 
+const ARWEAVE_PUBLIC_EXPONENT: u32 = 65537;
+
+/// multiAptos fields are fixed width but hold a variable number of keys:
+/// the owner is 32 key slots followed by the signing threshold, and the
+/// signature is 32 signature slots followed by a 4-byte bitmap of the slots
+/// actually signed.
+const MULTI_APTOS_SLOTS: usize = 32;
+const MULTI_APTOS_PUB_LENGTH: usize = MULTI_APTOS_SLOTS * 32 + 1;
+const MULTI_APTOS_SIG_LENGTH: usize = MULTI_APTOS_SLOTS * 64 + 4;
+
 pub struct SigConfig {
     pub sig_name: &'static str,
     pub sig_length: u32,
     pub pub_length: u32,
+    pub verify: fn(owner: &[u8], message: &[u8], signature: &[u8]) -> bool,
 }
 
 pub fn get_sig_types() -> HashMap<u64, SigConfig> {
@@ -14,6 +32,7 @@ pub fn get_sig_types() -> HashMap<u64, SigConfig> {
                 sig_name: "arweave",
                 sig_length: 512,
                 pub_length: 512,
+                verify: verify_arweave,
             },
         ),
         (
@@ -22,6 +41,7 @@ pub fn get_sig_types() -> HashMap<u64, SigConfig> {
                 sig_name: "ed25519",
                 sig_length: 64,
                 pub_length: 32,
+                verify: verify_ed25519,
             },
         ),
         (
@@ -30,6 +50,7 @@ pub fn get_sig_types() -> HashMap<u64, SigConfig> {
                 sig_name: "ethereum",
                 sig_length: 65,
                 pub_length: 65,
+                verify: verify_ethereum,
             },
         ),
         (
@@ -38,7 +59,281 @@ pub fn get_sig_types() -> HashMap<u64, SigConfig> {
                 sig_name: "solana",
                 sig_length: 64,
                 pub_length: 32,
+                verify: verify_ed25519,
+            },
+        ),
+        (
+            5,
+            SigConfig {
+                sig_name: "injectedAptos",
+                sig_length: 64,
+                pub_length: 32,
+                verify: verify_injected_aptos,
+            },
+        ),
+        (
+            6,
+            SigConfig {
+                sig_name: "multiAptos",
+                sig_length: MULTI_APTOS_SIG_LENGTH as u32,
+                pub_length: MULTI_APTOS_PUB_LENGTH as u32,
+                verify: verify_multi_aptos,
+            },
+        ),
+        (
+            7,
+            SigConfig {
+                sig_name: "typedEthereum",
+                sig_length: 65,
+                pub_length: 42,
+                verify: verify_typed_ethereum,
+            },
+        ),
+        (
+            101,
+            SigConfig {
+                sig_name: "kyve",
+                sig_length: 65,
+                pub_length: 65,
+                verify: verify_ethereum,
             },
         ),
     ])
 }
+
+/// RSA-PSS with SHA-256, where the owner is the 4096-bit modulus.
+/// Arweave signers use either the digest length (WebCrypto) or the maximum
+/// salt length (Node), so both are accepted.
+pub(crate) fn verify_arweave(owner: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let modulus = BigUint::from_bytes_be(owner);
+    let key = match RsaPublicKey::new(modulus, BigUint::from(ARWEAVE_PUBLIC_EXPONENT)) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+
+    let hashed = utils::sha256(message);
+    let max_salt_length = key.size().saturating_sub(hashed.len() + 2);
+
+    [hashed.len(), max_salt_length].into_iter().any(|salt_length| {
+        let scheme = Pss::new_with_salt::<Sha256>(salt_length);
+        key.verify(scheme, &hashed, signature).is_ok()
+    })
+}
+
+/// Plain ed25519 over the message, shared by ed25519 and solana signers.
+fn verify_ed25519(owner: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let key = match owner.try_into().map(ed25519_dalek::VerifyingKey::from_bytes) {
+        Ok(Ok(key)) => key,
+        _ => return false,
+    };
+
+    match ed25519_dalek::Signature::from_slice(signature) {
+        Ok(signature) => key.verify(message, &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// secp256k1 over the EIP-191 personal message hash, where the owner is the
+/// uncompressed public key and the signature is `r || s || v`.
+fn verify_ethereum(owner: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let key = match k256::ecdsa::VerifyingKey::from_sec1_bytes(owner) {
+        Ok(key) => key,
+        Err(_) => return false,
+    };
+
+    let signature = match signature.get(..64).map(k256::ecdsa::Signature::from_slice) {
+        Some(Ok(signature)) => signature.normalize_s().unwrap_or(signature),
+        _ => return false,
+    };
+
+    key.verify_prehash(&eip191_hash(message), &signature).is_ok()
+}
+
+fn eip191_hash(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+/// Aptos wallets sign the hex encoded message wrapped in their own envelope.
+fn verify_injected_aptos(owner: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    verify_ed25519(owner, &aptos_envelope(message), signature)
+}
+
+fn aptos_envelope(message: &[u8]) -> Vec<u8> {
+    format!("APTOS\nmessage: {}\nnonce: bundlr", hex::encode(message)).into_bytes()
+}
+
+/// Every slot flagged in the bitmap, most significant bit first, must hold a
+/// valid ed25519 signature of its key, and at least `threshold` must be set.
+fn verify_multi_aptos(owner: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    if owner.len() != MULTI_APTOS_PUB_LENGTH || signature.len() != MULTI_APTOS_SIG_LENGTH {
+        return false;
+    }
+
+    let threshold = owner[MULTI_APTOS_SLOTS * 32] as usize;
+    let bitmap = &signature[MULTI_APTOS_SLOTS * 64..];
+
+    let signed: Vec<usize> = (0..MULTI_APTOS_SLOTS)
+        .filter(|slot| bitmap[slot / 8] & (0x80 >> (slot % 8)) != 0)
+        .collect();
+
+    signed.len() >= threshold.max(1)
+        && signed.iter().all(|&slot| {
+            verify_ed25519(
+                &owner[slot * 32..(slot + 1) * 32],
+                message,
+                &signature[slot * 64..(slot + 1) * 64],
+            )
+        })
+}
+
+/// EIP-712 typed data signature, where the owner is the `0x` prefixed hex
+/// address of the signer, recovered from `r || s || v`.
+fn verify_typed_ethereum(owner: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let address = match std::str::from_utf8(owner)
+        .ok()
+        .and_then(|owner| owner.strip_prefix("0x"))
+        .and_then(|owner| hex::decode(owner).ok())
+        .and_then(|owner| <[u8; 20]>::try_from(owner).ok())
+    {
+        Some(address) => address,
+        None => return false,
+    };
+
+    let (signature, recovery_id) = match signature {
+        [rs @ .., v] if rs.len() == 64 => match k256::ecdsa::Signature::from_slice(rs) {
+            Ok(signature) => (signature, v.saturating_sub(27)),
+            Err(_) => return false,
+        },
+        _ => return false,
+    };
+    let recovery_id = match k256::ecdsa::RecoveryId::from_byte(recovery_id) {
+        Some(recovery_id) => recovery_id,
+        None => return false,
+    };
+
+    let digest = eip712_hash(message, &address);
+    match k256::ecdsa::VerifyingKey::recover_from_prehash(&digest, &signature, recovery_id) {
+        Ok(key) => ethereum_address(&key) == address,
+        Err(_) => false,
+    }
+}
+
+/// Hash of the `Bundlr(bytes Transaction hash,address address)` struct under
+/// the `Bundlr` version 1 domain, as signed by `eth_signTypedData_v4`.
+fn eip712_hash(message: &[u8], address: &[u8; 20]) -> [u8; 32] {
+    let domain_separator = keccak256(
+        &[
+            keccak256(b"EIP712Domain(string name,string version)"),
+            keccak256(b"Bundlr"),
+            keccak256(b"1"),
+        ]
+        .concat(),
+    );
+
+    let mut padded_address = [0; 32];
+    padded_address[12..].copy_from_slice(address);
+    let struct_hash = keccak256(
+        &[
+            keccak256(b"Bundlr(bytes Transaction hash,address address)"),
+            keccak256(message),
+            padded_address,
+        ]
+        .concat(),
+    );
+
+    keccak256(&[&[0x19, 0x01][..], &domain_separator, &struct_hash].concat())
+}
+
+fn ethereum_address(key: &k256::ecdsa::VerifyingKey) -> [u8; 20] {
+    let point = key.to_encoded_point(false);
+    keccak256(&point.as_bytes()[1..])[12..].try_into().unwrap()
+}
+
+fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::Signer;
+
+    #[test]
+    fn test_verify_ed25519() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let message = b"axer";
+        let signature = key.sign(message).to_bytes();
+        let owner = key.verifying_key().to_bytes();
+
+        assert!(verify_ed25519(&owner, message, &signature));
+        assert!(!verify_ed25519(&owner, b"forged", &signature));
+        assert!(!verify_ed25519(&owner[..31], message, &signature));
+    }
+
+    #[test]
+    fn test_verify_ethereum() {
+        let key = k256::ecdsa::SigningKey::from_slice(&[7; 32]).unwrap();
+        let message = b"axer";
+        let (signature, recovery_id) = key.sign_prehash_recoverable(&eip191_hash(message)).unwrap();
+
+        let mut signature = signature.to_vec();
+        signature.push(27 + recovery_id.to_byte());
+        let owner = key.verifying_key().to_encoded_point(false);
+
+        assert!(verify_ethereum(owner.as_bytes(), message, &signature));
+        assert!(!verify_ethereum(owner.as_bytes(), b"forged", &signature));
+    }
+
+    #[test]
+    fn test_verify_aptos() {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let message = b"axer";
+        let signature = key.sign(&aptos_envelope(message)).to_bytes();
+        let owner = key.verifying_key().to_bytes();
+
+        assert!(verify_injected_aptos(&owner, message, &signature));
+        assert!(!verify_injected_aptos(&owner, b"forged", &signature));
+
+        let keys: Vec<_> =
+            (1..=3).map(|seed| ed25519_dalek::SigningKey::from_bytes(&[seed; 32])).collect();
+        let mut owner = vec![0; MULTI_APTOS_PUB_LENGTH];
+        for (slot, key) in keys.iter().enumerate() {
+            owner[slot * 32..(slot + 1) * 32].copy_from_slice(key.verifying_key().as_bytes());
+        }
+        owner[MULTI_APTOS_SLOTS * 32] = 2;
+
+        // Slots 0 and 2 sign, slot 1 does not.
+        let mut signature = vec![0; MULTI_APTOS_SIG_LENGTH];
+        for slot in [0, 2] {
+            signature[slot * 64..(slot + 1) * 64]
+                .copy_from_slice(&keys[slot].sign(message).to_bytes());
+        }
+        signature[MULTI_APTOS_SLOTS * 64] = 0b1010_0000;
+
+        assert!(verify_multi_aptos(&owner, message, &signature));
+        assert!(!verify_multi_aptos(&owner, b"forged", &signature));
+
+        owner[MULTI_APTOS_SLOTS * 32] = 3;
+        assert!(!verify_multi_aptos(&owner, message, &signature));
+    }
+
+    #[test]
+    fn test_verify_typed_ethereum() {
+        let key = k256::ecdsa::SigningKey::from_slice(&[7; 32]).unwrap();
+        let address = ethereum_address(key.verifying_key());
+        let owner = format!("0x{}", hex::encode(address));
+        let message = b"axer";
+
+        let (signature, recovery_id) =
+            key.sign_prehash_recoverable(&eip712_hash(message, &address)).unwrap();
+        let mut signature = signature.to_vec();
+        signature.push(27 + recovery_id.to_byte());
+
+        assert!(verify_typed_ethereum(owner.as_bytes(), message, &signature));
+        assert!(!verify_typed_ethereum(owner.as_bytes(), b"forged", &signature));
+        assert!(!verify_typed_ethereum(b"0xnothex", message, &signature));
+    }
+}
@@ -0,0 +1,268 @@
+use crate::bundle::{
+    headers_end, items_len, parse_item, Bundle, BundleItem, Error, ItemDiagnostic, ItemHeader,
+    ParseOptions,
+};
+use apache_avro::Schema;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
+use base64::Engine;
+use futures::Stream;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Incremental bundle parser over an async reader.
+/// The item count and header table are read upfront, then every item is
+/// parsed as its bytes arrive, so at most one item is held in memory.
+/// In lenient mode failing items are skipped and kept as diagnostics.
+pub struct BundleStream<R> {
+    reader: R,
+    tags_schema: Schema,
+    options: ParseOptions,
+    headers: Vec<ItemHeader>,
+    next: usize,
+    offset: usize,
+    diagnostics: Vec<ItemDiagnostic>,
+}
+
+impl<R: AsyncRead + Unpin> BundleStream<R> {
+    pub async fn new(
+        mut reader: R,
+        tags_schema: &Schema,
+        options: &ParseOptions,
+    ) -> Result<Self, Error> {
+        let mut count = [0; 32];
+        read_exact(&mut reader, &mut count, Error::BundleLessThanMinimum).await?;
+
+        let items_len = items_len(&count)?;
+        let offset = headers_end(items_len)?;
+
+        let mut headers = vec![];
+        let mut diagnostics = vec![];
+        let mut items_start = offset;
+        for index in 0..items_len {
+            let mut header = [0; 64];
+            read_exact(&mut reader, &mut header, Error::BundleHeadersIncomplete).await?;
+
+            // The whole table is still read, so the item data starts where expected.
+            if !diagnostics.is_empty() {
+                continue;
+            }
+
+            match ItemHeader::from_bytes(&header) {
+                Ok(header) => {
+                    items_start = items_start.saturating_add(header.length);
+                    headers.push(header);
+                }
+                Err(error) if options.lenient => {
+                    // Without the item length, the offsets of the items after it are unknown.
+                    diagnostics.push(ItemDiagnostic {
+                        index,
+                        id: base64.encode(&header[32..64]),
+                        offset: items_start,
+                        parent_id: None,
+                        error,
+                    });
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(Self {
+            reader,
+            tags_schema: tags_schema.clone(),
+            options: options.clone(),
+            headers,
+            next: 0,
+            offset,
+            diagnostics,
+        })
+    }
+
+    pub fn headers(&self) -> &[ItemHeader] {
+        &self.headers
+    }
+
+    pub fn diagnostics(&self) -> &[ItemDiagnostic] {
+        &self.diagnostics
+    }
+
+    /// Reads and parses the next item, or returns `None` once every header
+    /// entry has been consumed.
+    pub async fn next_item(&mut self) -> Option<Result<BundleItem, Error>> {
+        loop {
+            let index = self.next;
+            let offset = self.offset;
+            let header = self.headers.get(index)?;
+
+            self.next += 1;
+            self.offset = self.offset.saturating_add(header.length);
+
+            // Reading through `take` grows the buffer as bytes arrive, instead of
+            // trusting the declared length for the allocation.
+            let mut item_data = vec![];
+            let read =
+                (&mut self.reader).take(header.length as u64).read_to_end(&mut item_data).await;
+
+            let result = match read {
+                Ok(n) if n < header.length => Err(Error::ItemDataIncomplete),
+                Ok(_) => {
+                    parse_item(&item_data, index, header, offset, &self.tags_schema, &self.options)
+                }
+                Err(e) => Err(e.into()),
+            };
+
+            match result {
+                Ok((item, diagnostics)) => {
+                    self.diagnostics.extend(diagnostics);
+                    return Some(Ok(item));
+                }
+                Err(error) if self.options.lenient => self.diagnostics.push(ItemDiagnostic {
+                    index,
+                    id: header.id.clone(),
+                    offset,
+                    parent_id: None,
+                    error,
+                }),
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+
+    pub fn into_stream(self) -> impl Stream<Item = Result<BundleItem, Error>> {
+        futures::stream::unfold(self, |mut stream| async move {
+            stream.next_item().await.map(|item| (item, stream))
+        })
+    }
+
+    /// Consumes the whole stream into a bundle.
+    pub async fn into_bundle(mut self) -> Result<Bundle, Error> {
+        let mut items = vec![];
+        while let Some(item) = self.next_item().await {
+            items.push(item?);
+        }
+
+        Ok(Bundle {
+            items,
+            diagnostics: self.diagnostics,
+        })
+    }
+
+    /// Hands back the reader, with whatever follows the last item read, and
+    /// the diagnostics recorded so far.
+    pub fn into_parts(self) -> (R, Vec<ItemDiagnostic>) {
+        (self.reader, self.diagnostics)
+    }
+}
+
+async fn read_exact<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut [u8],
+    eof_error: Error,
+) -> Result<(), Error> {
+    match reader.read_exact(buf).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(eof_error),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::TAGS_AVRO_SCHEMA;
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use tokio_util::io::StreamReader;
+
+    const TEST_BUNDLE: &str = "res/test_bundle";
+
+    fn chunked_reader(data: Vec<u8>) -> impl AsyncRead + Unpin {
+        let chunks: Vec<io::Result<Bytes>> =
+            data.chunks(7).map(|chunk| Ok(Bytes::copy_from_slice(chunk))).collect();
+        StreamReader::new(futures::stream::iter(chunks))
+    }
+
+    #[tokio::test]
+    async fn test_bundle_stream() {
+        let test_bundle = std::fs::read(TEST_BUNDLE).unwrap();
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let expected = Bundle::new(&test_bundle, &schema).unwrap();
+
+        let stream =
+            BundleStream::new(chunked_reader(test_bundle), &schema, &ParseOptions::default())
+                .await
+                .unwrap();
+        assert_eq!(stream.headers().len(), 2);
+
+        let items: Vec<BundleItem> = stream.into_stream().map(|item| item.unwrap()).collect().await;
+        assert!(items == expected.items);
+    }
+
+    #[tokio::test]
+    async fn test_bundle_stream_truncated() {
+        let mut test_bundle = std::fs::read(TEST_BUNDLE).unwrap();
+        test_bundle.truncate(test_bundle.len() - 1);
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+
+        let mut stream =
+            BundleStream::new(chunked_reader(test_bundle), &schema, &ParseOptions::default())
+                .await
+                .unwrap();
+
+        assert!(stream.next_item().await.unwrap().is_ok());
+        assert!(matches!(stream.next_item().await, Some(Err(Error::ItemDataIncomplete))));
+        assert!(stream.next_item().await.is_none());
+
+        let options = ParseOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let mut test_bundle = std::fs::read(TEST_BUNDLE).unwrap();
+        test_bundle.truncate(test_bundle.len() - 1);
+        let stream =
+            BundleStream::new(chunked_reader(test_bundle), &schema, &options).await.unwrap();
+        let bundle = stream.into_bundle().await.unwrap();
+        assert_eq!(bundle.items.len(), 1);
+        assert_eq!(bundle.diagnostics.len(), 1);
+        assert_eq!(bundle.diagnostics[0].index, 1);
+
+        let header_only = std::fs::read(TEST_BUNDLE).unwrap()[..40].to_vec();
+        let result =
+            BundleStream::new(chunked_reader(header_only), &schema, &ParseOptions::default()).await;
+        assert!(matches!(result, Err(Error::BundleHeadersIncomplete)));
+    }
+
+    #[tokio::test]
+    async fn test_bundle_stream_bad_header() {
+        // The second entry declares a length beyond usize.
+        let mut test_bundle = std::fs::read(TEST_BUNDLE).unwrap();
+        test_bundle[32 + 64 + 31] = 0xff;
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+
+        let result = BundleStream::new(
+            chunked_reader(test_bundle.clone()),
+            &schema,
+            &ParseOptions::default(),
+        )
+        .await;
+        assert!(matches!(result, Err(Error::ItemLengthTooLarge { .. })));
+
+        let options = ParseOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let expected = Bundle::with_options(&test_bundle, &schema, &options).unwrap();
+        let stream =
+            BundleStream::new(chunked_reader(test_bundle), &schema, &options).await.unwrap();
+        assert_eq!(stream.headers().len(), 1);
+
+        let bundle = stream.into_bundle().await.unwrap();
+        assert!(bundle.items == expected.items);
+        assert_eq!(bundle.items.len(), 1);
+        assert_eq!(bundle.diagnostics.len(), 1);
+
+        let (diagnostic, expected) = (&bundle.diagnostics[0], &expected.diagnostics[0]);
+        assert_eq!(diagnostic.index, 1);
+        assert_eq!((&diagnostic.id, diagnostic.offset), (&expected.id, expected.offset));
+        assert!(matches!(diagnostic.error, Error::ItemLengthTooLarge { .. }));
+    }
+}
@@ -1,8 +1,10 @@
-use apache_avro::{from_avro_datum, from_value, Schema};
+use apache_avro::types::Value;
+use apache_avro::{from_avro_datum, to_avro_datum, Schema};
 use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
 use base64::Engine;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+use std::str::FromStr;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -12,13 +14,132 @@ pub enum Error {
     Base64Error(#[from] base64::DecodeError),
     #[error("utf8 error: {0}")]
     Utf8Error(#[from] std::string::FromUtf8Error),
+    #[error("avro value is not a tag array")]
+    InvalidTagsValue,
+    #[error("{count} tags exceed the maximum of {MAX_TAGS}")]
+    TooManyTags {
+        count: u64,
+    },
+    #[error("tag count mismatch, expected {expected} but found {found}")]
+    TagCountMismatch {
+        expected: u64,
+        found: u64,
+    },
+    #[error("tag {index} name is {length} bytes, exceeding the maximum of {MAX_TAG_NAME_BYTES}")]
+    TagNameTooLong {
+        index: usize,
+        length: usize,
+    },
+    #[error("tag {index} value is {length} bytes, exceeding the maximum of {MAX_TAG_VALUE_BYTES}")]
+    TagValueTooLong {
+        index: usize,
+        length: usize,
+    },
+    #[error("{length} trailing bytes after the avro tags")]
+    TrailingBytes {
+        length: usize,
+    },
 }
 
+/// ANS-104 tag limits.
+pub const MAX_TAGS: u64 = 128;
+pub const MAX_TAG_NAME_BYTES: usize = 1024;
+pub const MAX_TAG_VALUE_BYTES: usize = 3072;
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Base64(pub String);
 impl Base64 {
+    pub fn encode(bytes: &[u8]) -> Self {
+        Self(base64.encode(bytes))
+    }
+
     pub fn decode(&self) -> Result<String, Error> {
-        Ok(String::from_utf8(base64.decode(&self.0.as_bytes())?)?)
+        Ok(String::from_utf8(base64.decode(self.0.as_bytes())?)?)
+    }
+
+    /// Decodes to a UTF-8 string, or re-encodes the bytes with the fallback
+    /// encoding when they are not valid UTF-8.
+    pub fn to_value(&self, fallback: TagEncoding) -> TagValue {
+        let bytes = match base64.decode(self.0.as_bytes()) {
+            Ok(bytes) => bytes,
+            Err(_) => {
+                return TagValue::Binary {
+                    encoding: TagEncoding::Base64,
+                    data: self.0.clone(),
+                }
+            }
+        };
+
+        match String::from_utf8(bytes) {
+            Ok(value) => TagValue::Utf8(value),
+            Err(e) => TagValue::Binary {
+                encoding: fallback,
+                data: fallback.encode(e.as_bytes()),
+            },
+        }
+    }
+}
+
+/// Encoding used for tag bytes that are not valid UTF-8.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagEncoding {
+    #[default]
+    Base64,
+    Hex,
+}
+
+impl TagEncoding {
+    fn encode(&self, bytes: &[u8]) -> String {
+        match self {
+            TagEncoding::Base64 => base64.encode(bytes),
+            TagEncoding::Hex => hex::encode(bytes),
+        }
+    }
+}
+
+impl FromStr for TagEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "base64" => Ok(TagEncoding::Base64),
+            "hex" => Ok(TagEncoding::Hex),
+            _ => Err(format!("unknown tag encoding: {s}, expected base64 or hex")),
+        }
+    }
+}
+
+impl Display for TagEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TagEncoding::Base64 => write!(f, "base64"),
+            TagEncoding::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+/// A decoded tag name or value, serialized as a plain string when it is
+/// UTF-8 and as `{"encoding": .., "data": ..}` otherwise.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum TagValue {
+    Utf8(String),
+    Binary {
+        encoding: TagEncoding,
+        data: String,
+    },
+}
+
+impl Display for TagValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TagValue::Utf8(value) => write!(f, "{value:?}"),
+            TagValue::Binary {
+                encoding,
+                data,
+            } => write!(f, "{encoding}:{data}"),
+        }
     }
 }
 
@@ -32,23 +153,234 @@ pub struct Tag<T> {
 pub struct Base64Tags(pub Vec<Tag<Base64>>);
 
 impl Base64Tags {
+    /// Parses the tags of a data item, checking them against its declared tag
+    /// count and the ANS-104 limits.
+    pub fn from_item(schema: &Schema, count: u64, bytes: &[u8]) -> Result<Self, Error> {
+        if count > MAX_TAGS {
+            return Err(Error::TooManyTags {
+                count,
+            });
+        }
+
+        let tags = match bytes.is_empty() {
+            true => Self(vec![]),
+            false => Self::from_avro(schema, bytes.to_vec())?,
+        };
+
+        tags.validate(count)?;
+        Ok(tags)
+    }
+
+    pub fn validate(&self, count: u64) -> Result<(), Error> {
+        let found = self.0.len() as u64;
+        if found != count {
+            return Err(Error::TagCountMismatch {
+                expected: count,
+                found,
+            });
+        }
+
+        for (index, tag) in self.0.iter().enumerate() {
+            let length = base64.decode(&tag.name.0)?.len();
+            if length > MAX_TAG_NAME_BYTES {
+                return Err(Error::TagNameTooLong {
+                    index,
+                    length,
+                });
+            }
+
+            let length = base64.decode(&tag.value.0)?.len();
+            if length > MAX_TAG_VALUE_BYTES {
+                return Err(Error::TagValueTooLong {
+                    index,
+                    length,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses avro encoded tags, which must span the whole input.
     pub fn from_avro(schema: &Schema, value: Vec<u8>) -> Result<Self, Error> {
         let mut b = value.as_slice();
-        let value = from_avro_datum(&schema, &mut b, None)?;
-        Ok(from_value::<Base64Tags>(&value)?)
+        let value = from_avro_datum(schema, &mut b, None)?;
+
+        if !b.is_empty() {
+            return Err(Error::TrailingBytes {
+                length: b.len(),
+            });
+        }
+
+        let items = match value {
+            Value::Array(items) => items,
+            _ => return Err(Error::InvalidTagsValue),
+        };
+
+        let tags = items
+            .iter()
+            .map(|item| {
+                Ok(Tag {
+                    name: Base64::encode(record_bytes(item, "name")?),
+                    value: Base64::encode(record_bytes(item, "value")?),
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Self(tags))
+    }
+
+    /// Encodes the tags into avro bytes, leaving them empty when there are no
+    /// tags as ANS-104 signers do.
+    pub fn to_avro(&self, schema: &Schema) -> Result<Vec<u8>, Error> {
+        if self.0.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let items = self
+            .0
+            .iter()
+            .map(|tag| {
+                Ok(Value::Record(vec![
+                    ("name".to_string(), Value::Bytes(base64.decode(&tag.name.0)?)),
+                    ("value".to_string(), Value::Bytes(base64.decode(&tag.value.0)?)),
+                ]))
+            })
+            .collect::<Result<Vec<Value>, Error>>()?;
+
+        Ok(to_avro_datum(schema, Value::Array(items))?)
+    }
+
+    pub fn decoded(&self, fallback: TagEncoding) -> Vec<Tag<TagValue>> {
+        self.0
+            .iter()
+            .map(|tag| Tag {
+                name: tag.name.to_value(fallback),
+                value: tag.value.to_value(fallback),
+            })
+            .collect()
+    }
+
+    /// Returns the UTF-8 value of the first tag with the given name.
+    pub fn get(&self, name: &str) -> Option<String> {
+        self.0
+            .iter()
+            .find(|tag| tag.name.decode().is_ok_and(|n| n == name))
+            .and_then(|tag| tag.value.decode().ok())
+    }
+}
+
+fn record_bytes<'a>(record: &'a Value, field: &str) -> Result<&'a [u8], Error> {
+    match record {
+        Value::Record(fields) => match fields.iter().find(|(name, _)| name == field) {
+            Some((_, Value::Bytes(bytes))) => Ok(bytes),
+            _ => Err(Error::InvalidTagsValue),
+        },
+        _ => Err(Error::InvalidTagsValue),
     }
 }
 
 impl Display for Base64Tags {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        for tag in &self.0 {
-            write!(
-                f,
-                "{:?}={:?};",
-                tag.name.decode().unwrap(),
-                tag.value.decode().unwrap()
-            )?;
+        for tag in self.decoded(TagEncoding::Base64) {
+            write!(f, "{}={};", tag.name, tag.value)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::TAGS_AVRO_SCHEMA;
+
+    fn tags_of(tags: &[(&[u8], &[u8])]) -> Base64Tags {
+        Base64Tags(
+            tags.iter()
+                .map(|(name, value)| Tag {
+                    name: Base64::encode(name),
+                    value: Base64::encode(value),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_tags_validation() {
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let tags = tags_of(&[(b"Content-Type", b"text/plain")]);
+        let bytes = tags.to_avro(&schema).unwrap();
+
+        assert_eq!(Base64Tags::from_item(&schema, 1, &bytes).unwrap(), tags);
+        assert!(Base64Tags::from_item(&schema, 0, &[]).unwrap().0.is_empty());
+
+        let result = Base64Tags::from_item(&schema, 2, &bytes);
+        assert!(matches!(
+            result,
+            Err(Error::TagCountMismatch {
+                expected: 2,
+                found: 1
+            })
+        ));
+
+        let result = Base64Tags::from_item(&schema, 1, &[]);
+        assert!(matches!(result, Err(Error::TagCountMismatch { .. })));
+
+        let result = Base64Tags::from_item(&schema, 129, &bytes);
+        assert!(matches!(
+            result,
+            Err(Error::TooManyTags {
+                count: 129
+            })
+        ));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        let result = Base64Tags::from_item(&schema, 1, &trailing);
+        assert!(matches!(
+            result,
+            Err(Error::TrailingBytes {
+                length: 1
+            })
+        ));
+
+        let result = tags_of(&[(&[b'n'; 1025], b"v")]).validate(1);
+        assert!(matches!(
+            result,
+            Err(Error::TagNameTooLong {
+                index: 0,
+                length: 1025
+            })
+        ));
+
+        let result = tags_of(&[(b"n", b"v"), (b"n", &[b'v'; 3073])]).validate(2);
+        assert!(matches!(
+            result,
+            Err(Error::TagValueTooLong {
+                index: 1,
+                length: 3073
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decoded_tags() {
+        let tags = tags_of(&[(b"Content-Type", b"text/plain"), (b"Raw", &[0xff, 0x00])]);
+
+        let decoded = tags.decoded(TagEncoding::Hex);
+        assert_eq!(decoded[0].value, TagValue::Utf8("text/plain".to_string()));
+        assert_eq!(
+            decoded[1].value,
+            TagValue::Binary {
+                encoding: TagEncoding::Hex,
+                data: "ff00".to_string(),
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&decoded[1]).unwrap(),
+            r#"{"name":"Raw","value":{"encoding":"hex","data":"ff00"}}"#
+        );
+
+        assert_eq!(tags.to_string(), r#""Content-Type"="text/plain";"Raw"=base64:_wA;"#);
+    }
+}
@@ -1,7 +1,12 @@
-use crate::bundle::tags::Base64Tags;
+use crate::bundle::deep_hash::{transaction_signature_data, TransactionFields};
+use crate::bundle::signatures::verify_arweave;
+use crate::bundle::tags;
+use crate::bundle::tags::{Base64Tags, Tag};
+use crate::utils;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
-use crate::bundle::tags;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -19,10 +24,28 @@ pub enum Error {
 
     #[error("tags error: {0}")]
     TagsError(#[from] tags::Error),
+
+    #[error("invalid data size '{0}'")]
+    InvalidDataSize(String),
+
+    #[error("base64 error: {0}")]
+    Base64Error(#[from] base64::DecodeError),
+
+    #[error("unsupported transaction format {0}, only format 2 can be verified")]
+    UnsupportedTxFormat(u8),
+
+    #[error("invalid transaction signature")]
+    InvalidSignature,
+
+    #[error("transaction id mismatch, expected {expected} but found '{found}'")]
+    InvalidId {
+        expected: String,
+        found: String,
+    },
 }
 
-const BUNDLE_FORMAT_SUPPORTED: &str = "binary";
-const BUNDLE_VERSION_SUPPORTED: &str = "2.0.0";
+pub const BUNDLE_FORMAT_SUPPORTED: &str = "binary";
+pub const BUNDLE_VERSION_SUPPORTED: &str = "2.0.0";
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct BundleTx {
@@ -41,20 +64,10 @@ pub struct BundleTx {
 }
 
 impl BundleTx {
+    /// Checks the bundle tags, then the transaction signature and id.
     pub fn is_valid(&self) -> Result<(), Error> {
-        let mut format = "".to_string();
-        let mut version = "".to_string();
-
-        self.tags.0.iter().for_each(|tag| {
-            let name = tag.name.decode().unwrap();
-            let value = tag.value.decode().unwrap();
-
-            if name == "Bundle-Format" {
-                format = value;
-            } else if name == "Bundle-Version" {
-                version = value;
-            }
-        });
+        let format = self.tags.get("Bundle-Format").unwrap_or_default();
+        let version = self.tags.get("Bundle-Version").unwrap_or_default();
 
         if format != BUNDLE_FORMAT_SUPPORTED {
             return Err(Error::InvalidBundleFormat {
@@ -70,8 +83,63 @@ impl BundleTx {
             });
         }
 
+        self.verify()
+    }
+
+    /// Verifies the RSA-PSS signature of the owner over the format 2 deep-hash
+    /// of the transaction fields, and that the id is the hash of the signature.
+    pub fn verify(&self) -> Result<(), Error> {
+        if self.format != 2 {
+            return Err(Error::UnsupportedTxFormat(self.format));
+        }
+
+        let tags = self
+            .tags
+            .0
+            .iter()
+            .map(|tag| {
+                Ok(Tag {
+                    name: base64.decode(&tag.name.0)?,
+                    value: base64.decode(&tag.value.0)?,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let owner = base64.decode(&self.owner)?;
+        let message = transaction_signature_data(&TransactionFields {
+            owner: &owner,
+            target: &base64.decode(&self.target)?,
+            quantity: &self.quantity,
+            reward: &self.reward,
+            last_tx: &base64.decode(&self.last_tx)?,
+            tags: &tags,
+            data_size: &self.data_size,
+            data_root: &self.data_root_bytes()?,
+        });
+
+        let signature = base64.decode(&self.signature)?;
+        if !verify_arweave(&owner, &message, &signature) {
+            return Err(Error::InvalidSignature);
+        }
+
+        let id = base64.encode(utils::sha256(&signature));
+        if id != self.id {
+            return Err(Error::InvalidId {
+                expected: id,
+                found: self.id.clone(),
+            });
+        }
+
         Ok(())
     }
+
+    pub fn data_size_bytes(&self) -> Result<usize, Error> {
+        self.data_size.parse().map_err(|_| Error::InvalidDataSize(self.data_size.clone()))
+    }
+
+    pub fn data_root_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(base64.decode(&self.data_root)?)
+    }
 }
 
 impl Display for BundleTx {
@@ -83,3 +151,80 @@ impl Display for BundleTx {
         )
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::bundle::builder::Signer;
+    use crate::bundle::tags::Base64;
+
+    /// Bundle transaction signed by the signer, with a random id.
+    pub(crate) fn signed_tx(signer: &Signer) -> BundleTx {
+        let tag = |name: &str, value: &str| Tag {
+            name: Base64::encode(name.as_bytes()),
+            value: Base64::encode(value.as_bytes()),
+        };
+
+        let mut tx = BundleTx {
+            format: 2,
+            id: String::new(),
+            last_tx: base64.encode([1; 48]),
+            owner: base64.encode(signer.owner()),
+            tags: Base64Tags(vec![
+                tag("Bundle-Format", BUNDLE_FORMAT_SUPPORTED),
+                tag("Bundle-Version", BUNDLE_VERSION_SUPPORTED),
+            ]),
+            target: String::new(),
+            quantity: "0".to_string(),
+            data: String::new(),
+            data_root: base64.encode([2; 32]),
+            data_size: "1024".to_string(),
+            reward: "12345".to_string(),
+            signature: String::new(),
+        };
+
+        let tags: Vec<Tag<Vec<u8>>> = tx
+            .tags
+            .0
+            .iter()
+            .map(|tag| Tag {
+                name: base64.decode(&tag.name.0).unwrap(),
+                value: base64.decode(&tag.value.0).unwrap(),
+            })
+            .collect();
+        let message = transaction_signature_data(&TransactionFields {
+            owner: &signer.owner(),
+            target: &[],
+            quantity: &tx.quantity,
+            reward: &tx.reward,
+            last_tx: &[1; 48],
+            tags: &tags,
+            data_size: &tx.data_size,
+            data_root: &[2; 32],
+        });
+
+        let signature = signer.sign(&message).unwrap();
+        tx.id = base64.encode(utils::sha256(&signature));
+        tx.signature = base64.encode(signature);
+        tx
+    }
+
+    #[test]
+    fn test_verify_tx() {
+        let signer = Signer::from_keyfile("res/test_wallet.json").unwrap();
+        let tx = signed_tx(&signer);
+        assert!(tx.is_valid().is_ok());
+
+        let mut tampered = tx.clone();
+        tampered.reward = "1".to_string();
+        assert!(matches!(tampered.verify(), Err(Error::InvalidSignature)));
+
+        let mut tampered = tx.clone();
+        tampered.id = base64.encode([0; 32]);
+        assert!(matches!(tampered.verify(), Err(Error::InvalidId { .. })));
+
+        let mut tampered = tx.clone();
+        tampered.format = 1;
+        assert!(matches!(tampered.verify(), Err(Error::UnsupportedTxFormat(1))));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::bundle::{Bundle, BundleItem, Error};
+use crate::utils::U256;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
+use base64::Engine;
+use std::io::Write;
+
+/// Binary fields of a data item, in the order they are laid out.
+/// Empty target or anchor are written as absent, and `tags` holds the avro
+/// encoded tags.
+pub struct ItemFields<'a> {
+    pub signature_type: u16,
+    pub signature: &'a [u8],
+    pub owner: &'a [u8],
+    pub target: &'a [u8],
+    pub anchor: &'a [u8],
+    pub tags_count: usize,
+    pub tags: &'a [u8],
+    pub data: &'a [u8],
+}
+
+/// Encodes data item fields into the ANS-104 binary layout.
+pub fn encode_item(fields: &ItemFields) -> Vec<u8> {
+    let mut bytes = vec![];
+    bytes.extend_from_slice(&fields.signature_type.to_le_bytes());
+    bytes.extend_from_slice(fields.signature);
+    bytes.extend_from_slice(fields.owner);
+
+    for optional in [fields.target, fields.anchor] {
+        match optional.is_empty() {
+            true => bytes.push(0),
+            false => {
+                bytes.push(1);
+                bytes.extend_from_slice(optional);
+            }
+        }
+    }
+
+    bytes.extend_from_slice(&(fields.tags_count as u64).to_le_bytes());
+    bytes.extend_from_slice(&(fields.tags.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(fields.tags);
+    bytes.extend_from_slice(fields.data);
+    bytes
+}
+
+impl BundleItem {
+    /// Encodes the item back into its ANS-104 binary layout, with its tags
+    /// as originally encoded so the signature still holds.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(encode_item(&ItemFields {
+            signature_type: self.signature_type,
+            signature: &base64.decode(&self.signature)?,
+            owner: &base64.decode(&self.owner)?,
+            target: &base64.decode(&self.target)?,
+            anchor: &base64.decode(&self.anchor)?,
+            tags_count: self.tags.0.len(),
+            tags: &self.tag_bytes,
+            data: &self.data,
+        }))
+    }
+}
+
+/// Writes items as an ANS-104 bundle: the 32-byte item count, a 64-byte
+/// header entry of length and id per item, and then the items themselves.
+pub fn write_bundle<W: Write>(writer: &mut W, items: &[BundleItem]) -> Result<(), Error> {
+    let encoded =
+        items.iter().map(|item| item.to_bytes()).collect::<Result<Vec<Vec<u8>>, Error>>()?;
+
+    writer.write_all(&U256::from(items.len()).to_le_bytes())?;
+
+    for (item, bytes) in items.iter().zip(encoded.iter()) {
+        writer.write_all(&U256::from(bytes.len()).to_le_bytes())?;
+        writer.write_all(&base64.decode(&item.id)?)?;
+    }
+
+    for bytes in encoded {
+        writer.write_all(&bytes)?;
+    }
+
+    Ok(())
+}
+
+impl Bundle {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = vec![];
+        write_bundle(&mut bytes, &self.items)?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::deep_hash::data_item_signature_data;
+    use crate::client::TAGS_AVRO_SCHEMA;
+    use apache_avro::Schema;
+    use ed25519_dalek::Signer;
+
+    const TEST_BUNDLE: &str = "res/test_bundle";
+
+    #[test]
+    fn test_write_bundle() {
+        let test_bundle = std::fs::read(TEST_BUNDLE).unwrap();
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let bundle = Bundle::new(&test_bundle, &schema).unwrap();
+
+        assert_eq!(bundle.to_bytes().unwrap(), test_bundle);
+
+        let mut subset = vec![];
+        write_bundle(&mut subset, &bundle.items[1..]).unwrap();
+        let parsed = Bundle::new(&subset, &schema).unwrap();
+        assert_eq!(parsed.items.len(), 1);
+        assert!(parsed.items[0] == bundle.items[1]);
+    }
+
+    /// Avro array of two blocks of one tag each, where `to_avro` would write a
+    /// single block of two tags.
+    fn multi_block_tags() -> Vec<u8> {
+        let mut bytes = vec![];
+        for (name, value) in [("a", "1"), ("b", "2")] {
+            // Block and byte lengths are zigzag encoded.
+            bytes.push(2);
+            for field in [name, value] {
+                bytes.push(2 * field.len() as u8);
+                bytes.extend_from_slice(field.as_bytes());
+            }
+        }
+        bytes.push(0);
+        bytes
+    }
+
+    #[test]
+    fn test_write_multi_block_tags() {
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+        let owner = key.verifying_key().to_bytes();
+        let tags = multi_block_tags();
+
+        let message = data_item_signature_data(2, &owner, &[], &[], &tags, b"data");
+        let signature = key.sign(&message).to_bytes();
+        let bytes = encode_item(&ItemFields {
+            signature_type: 2,
+            signature: &signature,
+            owner: &owner,
+            target: &[],
+            anchor: &[],
+            tags_count: 2,
+            tags: &tags,
+            data: b"data",
+        });
+
+        let item = BundleItem::new(&bytes, &schema).unwrap();
+        assert!(item.verified);
+        assert_eq!(item.tags.get("b"), Some("2".to_string()));
+        assert_ne!(item.tags.to_avro(&schema).unwrap(), tags);
+        assert_eq!(item.to_bytes().unwrap(), bytes);
+    }
+}
@@ -1,12 +1,16 @@
+use crate::utils::file;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
+use tracing::error;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("io error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("json error: {0}")]
+    JsonError(#[from] serde_json::Error),
 }
 
 #[derive(Deserialize)]
@@ -17,27 +21,37 @@ pub struct Cache<T: Serialize> {
 
 impl<T: Serialize> Drop for Cache<T> {
     fn drop(&mut self) {
-        let file = std::fs::File::create(self.file.as_str()).unwrap();
-        serde_json::to_writer(file, &self.data).unwrap();
+        if let Err(e) = self.save() {
+            error!("failed to save {}: {e}", self.file);
+        }
+    }
+}
+
+impl<T: Serialize> Cache<T> {
+    /// Writes the data to the file now, instead of waiting for the drop.
+    /// The file is replaced at once, so an interrupted save keeps the last one.
+    pub fn save(&self) -> Result<(), Error> {
+        file::write_json(Path::new(&self.file), &self.data)?;
+        Ok(())
     }
 }
 
 impl<T: Serialize + DeserializeOwned> Cache<T> {
-    pub fn from_file(file: String) -> Self {
+    pub fn from_file(file: String) -> Result<Self, Error> {
         let path = Path::new(file.as_str());
         let mut data = HashMap::<String, T>::new();
 
         if path.exists() {
-            let file = std::fs::read_to_string(path).unwrap();
-            data = serde_json::from_reader(file.as_bytes()).unwrap();
-        } else {
-            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            let json = std::fs::read_to_string(path)?;
+            data = serde_json::from_str(&json)?;
+        } else if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
         }
 
-        Self {
+        Ok(Self {
             data,
             file,
-        }
+        })
     }
 }
 
@@ -64,7 +78,7 @@ mod tests {
     fn test_cache() {
         let _d = Dropper;
         {
-            let mut cache = Cache::<Test>::from_file(TEST_CACHE.to_string());
+            let mut cache = Cache::<Test>::from_file(TEST_CACHE.to_string()).unwrap();
             cache.data.insert(
                 "1".to_string(),
                 Test {
@@ -73,7 +87,15 @@ mod tests {
             );
         }
 
-        let cache = Cache::<Test>::from_file(TEST_CACHE.to_string());
+        let cache = Cache::<Test>::from_file(TEST_CACHE.to_string()).unwrap();
         assert_eq!(cache.data.get("1").unwrap().name, "duck");
+        drop(cache);
+
+        // A truncated file fails to load instead of panicking.
+        fs::write(TEST_CACHE, r#"{"1":{"name":"du"#).unwrap();
+        assert!(matches!(
+            Cache::<Test>::from_file(TEST_CACHE.to_string()),
+            Err(Error::JsonError(_))
+        ));
     }
 }
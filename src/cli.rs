@@ -1,36 +1,94 @@
+use crate::bundle;
+use crate::bundle::extract::Extractor;
+use crate::bundle::tags::TagEncoding;
 use crate::bundle::tx::BundleTx;
-use crate::bundle::Bundle;
+use crate::bundle::{Bundle, BundleItem, ItemDiagnostic, ParseOptions};
+use crate::cache;
 use crate::cache::Cache;
 use crate::client;
-use crate::client::{Client, DEFAULT_BASE_URL, DEFAULT_TIMEOUT_MS};
-use crate::utils::file;
+use crate::client::gateways::{GatewayPool, GatewayStrategy};
+use crate::client::graphql::{TagFilter, TransactionQuery, MAX_PAGE_SIZE};
+use crate::client::peers::{PeerInfo, PeerPool};
+use crate::client::rate_limit::{RateLimiter, MIN_RATE};
+use crate::client::retry::{
+    RetryPolicy, DEFAULT_MAX_ATTEMPTS, DEFAULT_RETRY_DELAY_MS, DEFAULT_RETRY_JITTER_MS,
+};
+use crate::client::{
+    Client, VerifiedBundleStream, DEFAULT_BASE_URL, DEFAULT_CONCURRENCY, DEFAULT_TIMEOUT_MS,
+};
+use crate::utils::file::JsonArrayWriter;
 use argh::FromArgs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::io::{BufRead, Write};
 use std::path::{Path, PathBuf};
-use tokio::task::JoinSet;
-use tracing::{error, info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::{self, JoinError, JoinSet};
+use tracing::{debug, error, info, warn};
+use url::Url;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("client error: {0}")]
     ClientError(#[from] client::Error),
+    #[error("bundle error: {0}")]
+    BundleError(#[from] bundle::Error),
     #[error("args error")]
     ArgsError,
     #[error("io error: {0}")]
     IoError(#[from] std::io::Error),
+    #[error("url error: {0}")]
+    UrlError(#[from] url::ParseError),
+    #[error("cache error: {0}")]
+    CacheError(#[from] cache::Error),
+    #[error("{0} bundles failed")]
+    BatchError(usize),
 }
 
 #[derive(FromArgs, Debug, Clone)]
 /// Axer CLI - Arweave bundle explorer.
 pub struct Args {
-    /// network base url
-    #[argh(option, default = "default_base_url()")]
-    pub url: String,
+    /// network base url, repeat it to spread requests over several gateways
+    /// or nodes, defaults to https://arweave.net
+    #[argh(option)]
+    pub url: Vec<String>,
+
+    /// file with one gateway or node url per line, added to --url
+    #[argh(option)]
+    pub gateways_file: Option<String>,
+
+    /// how gateways are picked, round-robin or lowest-latency
+    #[argh(option, default = "GatewayStrategy::RoundRobin")]
+    pub gateway_strategy: GatewayStrategy,
 
     /// network timeout in ms
     #[argh(option, default = "default_timeout_ms()")]
     pub timeout: u64,
 
+    /// attempts per request on transient failures, 1 disables retries
+    #[argh(option, default = "DEFAULT_MAX_ATTEMPTS")]
+    pub max_attempts: u32,
+
+    /// base retry delay in ms, doubled on every retry
+    #[argh(option, default = "DEFAULT_RETRY_DELAY_MS")]
+    pub retry_delay: u64,
+
+    /// maximum random jitter in ms added to every retry delay
+    #[argh(option, default = "DEFAULT_RETRY_JITTER_MS")]
+    pub retry_jitter: u64,
+
+    /// maximum requests per second over all gateways and peers, at least
+    /// 0.001, 0 disables the limit
+    #[argh(option, default = "0.0", from_str_fn(parse_rate_limit))]
+    pub rate_limit: f64,
+
+    /// bundles fetched at the same time in batch mode, also the requests sent
+    /// at the same time for chunks and block transactions
+    #[argh(option, default = "DEFAULT_CONCURRENCY")]
+    pub concurrency: usize,
+
     /// index db filename
     #[argh(option, default = "default_db_filename()")]
     pub db_file: String,
@@ -43,6 +101,10 @@ pub struct Args {
     #[argh(option)]
     pub tx_id: Option<String>,
 
+    /// data item ID to fetch alone from the --tx-id bundle with range requests
+    #[argh(option)]
+    pub item_id: Option<String>,
+
     /// batch filename, enables batch mode
     #[argh(option, short = 'b')]
     pub batch_file: Option<String>,
@@ -50,10 +112,108 @@ pub struct Args {
     /// enables interactive mode
     #[argh(switch, short = 'i')]
     pub interactive: bool,
+
+    /// download bundle data by chunks, for nodes without the data endpoint
+    #[argh(switch)]
+    pub chunks: bool,
+
+    /// levels of nested bundles to unpack, 0 disables unpacking
+    #[argh(option, default = "0")]
+    pub max_depth: u32,
+
+    /// skip malformed bundle items instead of failing the whole bundle
+    #[argh(switch)]
+    pub lenient: bool,
+
+    /// add UTF-8 decoded tags to the output
+    #[argh(switch)]
+    pub decode_tags: bool,
+
+    /// encoding for decoded tags that are not UTF-8, base64 or hex
+    #[argh(option, default = "TagEncoding::Base64")]
+    pub tags_fallback: TagEncoding,
+
+    /// print the byte layout of every item and add it to the output
+    #[argh(switch)]
+    pub layout: bool,
+
+    /// write the data of every item to <out-dir>/<tx-id>/ with a manifest
+    #[argh(switch)]
+    pub extract: bool,
+
+    /// fall back to Arweave peers for data and chunks the gateways miss
+    #[argh(switch)]
+    pub peers: bool,
+
+    /// peer state filename, used with --peers
+    #[argh(option, default = "default_peers_filename()")]
+    pub peers_file: String,
+
+    /// new peers to probe at startup with --peers, 0 only refreshes known ones
+    #[argh(option, default = "DEFAULT_DISCOVER_PEERS")]
+    pub discover_peers: usize,
+
+    /// enables query mode, bundles found through the gateway graphql endpoint
+    /// are fetched as a batch
+    #[argh(switch, short = 'q')]
+    pub query: bool,
+
+    /// extra tag filter for query mode as name=value or name=value1,value2,
+    /// repeatable
+    #[argh(option)]
+    pub query_tag: Vec<TagFilter>,
+
+    /// bundle owner address for query mode, repeatable
+    #[argh(option)]
+    pub query_owner: Vec<String>,
+
+    /// lowest block height for query and crawl modes
+    #[argh(option)]
+    pub min_height: Option<u64>,
+
+    /// highest block height for query and crawl modes
+    #[argh(option)]
+    pub max_height: Option<u64>,
+
+    /// maximum bundles found in query mode
+    #[argh(option, default = "DEFAULT_QUERY_LIMIT")]
+    pub query_limit: usize,
+
+    /// enables crawl mode, every bundle of the blocks from --min-height to
+    /// --max-height is fetched
+    #[argh(switch)]
+    pub crawl: bool,
+
+    /// crawl progress filename, a crawl of the same heights resumes from it
+    #[argh(option, default = "default_checkpoint_filename()")]
+    pub checkpoint_file: String,
+}
+
+impl Args {
+    /// The `--url` gateways followed by the ones of the gateways file, or the
+    /// default gateway when none is given.
+    pub fn gateways(&self) -> Result<Vec<Url>, Error> {
+        let mut urls = self.url.clone();
+
+        if let Some(gateways_file) = &self.gateways_file {
+            let content = std::fs::read_to_string(gateways_file)?;
+            let lines = content.lines().map(str::trim);
+            urls.extend(lines.filter(|l| !l.is_empty() && !l.starts_with('#')).map(String::from));
+        }
+
+        if urls.is_empty() {
+            urls.push(DEFAULT_BASE_URL.to_string());
+        }
+
+        Ok(urls.iter().map(|url| Url::parse(url)).collect::<Result<_, _>>()?)
+    }
 }
 
-fn default_base_url() -> String {
-    DEFAULT_BASE_URL.to_string()
+fn parse_rate_limit(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(rate) if rate == 0.0 || RateLimiter::new(rate, 1).is_some() => Ok(rate),
+        _ => Err(format!("invalid rate limit: {value}, expected 0 or at least {MIN_RATE}")),
+    }
 }
 
 fn default_timeout_ms() -> u64 {
@@ -68,24 +228,63 @@ fn default_out_dir() -> String {
     "out/".to_string()
 }
 
+fn default_checkpoint_filename() -> String {
+    "crawl.json".to_string()
+}
+
+fn default_peers_filename() -> String {
+    "peers.json".to_string()
+}
+
+const DEFAULT_DISCOVER_PEERS: usize = 20;
+const DEFAULT_QUERY_LIMIT: usize = 100;
+
 pub struct Cli {
     client: Client,
     args: Args,
     cache: Cache<BundleTx>,
+    peers_cache: Option<Cache<PeerInfo>>,
 }
 
 impl Cli {
-    pub fn from_env_args() -> Self {
-        let args: Args = argh::from_env();
+    pub fn from_env_args() -> Result<Self, Error> {
+        Self::new(argh::from_env())
+    }
 
+    pub fn new(args: Args) -> Result<Self, Error> {
         let a = args.clone();
-        let client = Client::new(a.url, a.timeout);
+        let gateways = a.gateways()?;
+        let peers_cache =
+            a.peers.then(|| Cache::<PeerInfo>::from_file(a.peers_file)).transpose()?;
+        let peers = peers_cache.as_ref().map(|cache| cache.data.values().cloned());
+
+        let mut client = Client::new(gateways[0].to_string(), a.timeout)?
+            .with_gateways(GatewayPool::new(gateways, a.gateway_strategy))
+            .with_parse_options(ParseOptions {
+                max_depth: a.max_depth,
+                lenient: a.lenient,
+                layout: a.layout,
+            })
+            .with_chunked_data(a.chunks)
+            .with_retry_policy(RetryPolicy {
+                max_attempts: a.max_attempts,
+                base_delay: Duration::from_millis(a.retry_delay),
+                max_jitter: Duration::from_millis(a.retry_jitter),
+            })
+            .with_peers(PeerPool::new(peers.into_iter().flatten()))
+            .with_concurrency(a.concurrency);
+
+        // Allow a second worth of requests at once, a zero rate has no limiter.
+        if let Some(rate_limiter) = RateLimiter::new(a.rate_limit, a.rate_limit.ceil() as u32) {
+            client = client.with_rate_limiter(rate_limiter);
+        }
 
-        Self {
+        Ok(Self {
             client,
             args,
-            cache: Cache::from_file(a.db_file),
-        }
+            cache: Cache::from_file(a.db_file)?,
+            peers_cache,
+        })
     }
 
     pub async fn run(&mut self) -> Result<(), Error> {
@@ -94,7 +293,24 @@ impl Cli {
         let info = self.client.get_network_info().await?;
         info!("connected to: {info}");
 
-        if let Some(batch_file) = self.args.batch_file.clone() {
+        if self.args.peers {
+            match self.client.discover_peers(self.args.discover_peers).await {
+                Ok(count) => info!("{count} peers available"),
+                Err(e) => warn!("peer discovery failed: {e}"),
+            }
+        }
+
+        let result = self.run_mode().await;
+        self.save_peers();
+        result
+    }
+
+    async fn run_mode(&mut self) -> Result<(), Error> {
+        if self.args.crawl {
+            self.handle_crawl_mode().await?;
+        } else if self.args.query {
+            self.handle_query_mode().await?;
+        } else if let Some(batch_file) = self.args.batch_file.clone() {
             self.handle_batch_mode(batch_file).await?;
         } else {
             match self.args.interactive {
@@ -106,65 +322,224 @@ impl Cli {
         Ok(())
     }
 
+    async fn handle_crawl_mode(&mut self) -> Result<(), Error> {
+        let (Some(start), Some(end)) = (self.args.min_height, self.args.max_height) else {
+            error!("crawl mode requires --min-height and --max-height");
+            return Err(Error::ArgsError);
+        };
+
+        let key = format!("{start}-{end}");
+        let mut checkpoints =
+            Cache::<CrawlCheckpoint>::from_file(self.args.checkpoint_file.clone())?;
+        let mut checkpoint = checkpoints.data.get(&key).cloned().unwrap_or(CrawlCheckpoint {
+            height: start,
+            failed: vec![],
+        });
+
+        info!("running crawl mode from height {} to {end}", checkpoint.height);
+
+        if !checkpoint.failed.is_empty() {
+            info!("retrying {} transactions that failed before", checkpoint.failed.len());
+            checkpoint.failed = self.retry_failed(std::mem::take(&mut checkpoint.failed)).await;
+            checkpoints.data.insert(key.clone(), checkpoint.clone());
+            self.cache.save()?;
+            checkpoints.save()?;
+        }
+
+        // A failing transaction or bundle is recorded and skipped, only a
+        // block that cannot be fetched stops the crawl.
+        while checkpoint.height <= end {
+            let height = checkpoint.height;
+            let block = self.client.get_block_bundles(height).await?;
+            info!("found {} bundles in block {height}", block.bundles.len());
+
+            // Verified transactions go to the cache, so the batch only
+            // fetches their data.
+            let tx_ids: Vec<String> = block.bundles.iter().map(|tx| tx.id.clone()).collect();
+            for tx in block.bundles {
+                self.cache.data.insert(tx.id.clone(), tx);
+            }
+            let failed = self.handle_batch(tx_ids.into_iter()).await;
+
+            for id in block.failed.into_iter().chain(failed) {
+                if !checkpoint.failed.contains(&id) {
+                    checkpoint.failed.push(id);
+                }
+            }
+            checkpoint.height += 1;
+            checkpoints.data.insert(key.clone(), checkpoint.clone());
+            self.cache.save()?;
+            checkpoints.save()?;
+        }
+
+        if !checkpoint.failed.is_empty() {
+            warn!(
+                "{} transactions failed, they are listed in: {}",
+                checkpoint.failed.len(),
+                self.args.checkpoint_file
+            );
+        }
+
+        Ok(())
+    }
+
+    async fn handle_query_mode(&mut self) -> Result<(), Error> {
+        let mut query = TransactionQuery::bundles()
+            .first(self.args.query_limit.min(MAX_PAGE_SIZE as usize) as u32);
+        for filter in &self.args.query_tag {
+            query = query.tag_filter(filter.clone());
+        }
+        for owner in &self.args.query_owner {
+            query = query.owner(owner);
+        }
+        if self.args.min_height.is_some() || self.args.max_height.is_some() {
+            query = query.heights(self.args.min_height, self.args.max_height);
+        }
+
+        info!("running query mode with {:?}", query);
+
+        let nodes = self.client.query_all_transactions(&query, self.args.query_limit).await?;
+        info!("query found {} bundles", nodes.len());
+
+        let failed = self.handle_batch(nodes.into_iter().map(|node| node.id)).await;
+        batch_result(failed)
+    }
+
     async fn handle_batch_mode(&mut self, batch_file: String) -> Result<(), Error> {
         let file = std::fs::File::open(batch_file.as_str())?;
         let reader = std::io::BufReader::new(file).lines();
 
-        let mut set: JoinSet<Result<(BundleTx, Bundle), Error>> = JoinSet::new();
-
         info!("running batch mode using file: {batch_file}");
 
-        for line in reader.flatten() {
-            async fn fetch_bundle_data(
-                client: Client,
-                tx: BundleTx,
-            ) -> Result<(BundleTx, Bundle), Error> {
-                let bundle = client.get_bundle_data(&tx).await?;
-                Ok((tx, bundle))
-            }
+        let failed = self.handle_batch(reader.map_while(Result::ok)).await;
+        batch_result(failed)
+    }
 
+    /// Fetches and saves every bundle not saved yet, at most `--concurrency` at
+    /// once. A failing bundle is logged and does not stop the others, the ids
+    /// of the failed ones are returned.
+    async fn handle_batch(&mut self, tx_ids: impl Iterator<Item = String>) -> Vec<String> {
+        let mut set: JoinSet<Result<BundleTx, Error>> = JoinSet::new();
+        // Bundle of every task, so even a panicking one is recorded.
+        let mut tasks: HashMap<task::Id, String> = HashMap::new();
+        let semaphore = Arc::new(Semaphore::new(self.args.concurrency.max(1)));
+        let mut failed = vec![];
+
+        for line in tx_ids {
+            /// Fetches the transaction too, unless it is given.
             async fn fetch_bundle(
                 client: Client,
+                args: Args,
                 tx_id: String,
-            ) -> Result<(BundleTx, Bundle), Error> {
-                let (bundle_tx, bundle) = client.get_bundle(tx_id.as_str()).await?;
-                Ok((bundle_tx, bundle))
+                tx: Option<BundleTx>,
+            ) -> Result<BundleTx, Error> {
+                let (tx, stream) = match tx {
+                    Some(tx) => {
+                        let stream = client.stream_bundle_data(&tx).await?;
+                        (tx, stream)
+                    }
+                    None => client.stream_bundle(tx_id.as_str()).await?,
+                };
+
+                save_bundle_stream(&args, &tx_id, stream).await?;
+                Ok(tx)
             }
 
             let tx_id = line.trim().to_string();
 
-            if !self.cache.data.contains_key(&tx_id) {
-                set.spawn(fetch_bundle(self.client.clone(), tx_id.clone()));
-                continue;
-            }
+            let tx = match self.cache.data.get(&tx_id) {
+                Some(tx) => {
+                    info!("transaction {} was found in cache", tx_id);
 
-            let tx = self.cache.data.get(&tx_id).unwrap();
-            info!("transaction {} was found in cache", tx_id);
+                    if bundle_path(&self.args, &tx_id).exists() {
+                        continue;
+                    }
+                    warn!("bundle file not found, fetching: {}", tx_id);
+                    Some(tx.clone())
+                }
+                None => None,
+            };
 
-            let path = self.get_bundle_path(&tx_id);
-            if !path.exists() {
-                warn!("bundle file not found, fetching: {}", tx_id);
+            // Wait for a permit, handling the fetches that finish meanwhile.
+            let permit = loop {
+                tokio::select! {
+                    permit = semaphore.clone().acquire_owned() => {
+                        break permit.expect("semaphore is never closed");
+                    }
+                    Some(res) = set.join_next_with_id() => {
+                        self.handle_batch_result(res, &mut tasks, &mut failed);
+                    }
+                }
+            };
 
-                set.spawn(fetch_bundle_data(self.client.clone(), tx.clone()));
-            }
+            let (client, args, id) = (self.client.clone(), self.args.clone(), tx_id.clone());
+            let handle = set.spawn(async move {
+                let result = fetch_bundle(client, args, id, tx).await;
+                drop(permit);
+                result
+            });
+            tasks.insert(handle.id(), tx_id);
         }
 
-        while let Some(res) = set.join_next().await {
-            match res {
-                Ok(r) => {
-                    let (tx, bundle) = r?;
-                    info!("transaction: {}", tx);
+        while let Some(res) = set.join_next_with_id().await {
+            self.handle_batch_result(res, &mut tasks, &mut failed);
+        }
 
-                    self.cache.data.insert(tx.id.clone(), tx.clone());
-                    file::save_serde_json(self.get_bundle_path(&tx.id), &bundle.items)?;
+        failed
+    }
+
+    fn handle_batch_result(
+        &mut self,
+        res: Result<(task::Id, Result<BundleTx, Error>), JoinError>,
+        tasks: &mut HashMap<task::Id, String>,
+        failed: &mut Vec<String>,
+    ) {
+        let id = match &res {
+            Ok((id, _)) => *id,
+            Err(e) => e.id(),
+        };
+        let tx_id = tasks.remove(&id).unwrap_or_default();
+
+        match res {
+            Ok((_, Ok(tx))) => {
+                info!("transaction: {}", tx);
+
+                self.cache.data.insert(tx.id.clone(), tx);
+            }
+            Ok((_, Err(e))) => {
+                error!("failed to fetch bundle {tx_id}: {e}");
+                failed.push(tx_id);
+            }
+            Err(e) => {
+                error!("batch task for bundle {tx_id} failed: {e}");
+                failed.push(tx_id);
+            }
+        }
+    }
+
+    /// Retries transactions that failed in an earlier run of a crawl. Those
+    /// that turn out not to be bundles are dropped, the ids still failing are
+    /// returned.
+    async fn retry_failed(&mut self, tx_ids: Vec<String>) -> Vec<String> {
+        let mut bundles = vec![];
+        let mut failed = vec![];
+
+        for tx_id in tx_ids {
+            match self.client.get_tx(&tx_id).await.map(|tx| (tx.is_valid(), tx)) {
+                Ok((Ok(()), tx)) => {
+                    self.cache.data.insert(tx_id.clone(), tx);
+                    bundles.push(tx_id);
                 }
+                Ok((Err(e), _)) => debug!("dropping transaction {tx_id}, not a bundle: {e}"),
                 Err(e) => {
-                    error!("batch task join failed: {e}");
+                    warn!("failed to fetch transaction {tx_id}: {e}");
+                    failed.push(tx_id);
                 }
             }
         }
 
-        Ok(())
+        failed.extend(self.handle_batch(bundles.into_iter()).await);
+        failed
     }
 
     async fn handle_single(&mut self) -> Result<(), Error> {
@@ -172,6 +547,14 @@ impl Cli {
             Some(tx_id) => {
                 info!("running single mode for transaction: {tx_id}");
 
+                if let Some(item_id) = &self.args.item_id {
+                    info!("fetching item {item_id} with range requests");
+
+                    let bundle = self.client.get_bundle_item(tx_id, item_id).await?;
+                    save_bundle(&self.args, tx_id, item_path(&self.args, tx_id, item_id), bundle)?;
+                    return Ok(());
+                }
+
                 self.get_or_fetch_bundle(tx_id).await?;
                 Ok(())
             }
@@ -199,7 +582,7 @@ impl Cli {
             let tx_id = input.trim();
 
             match self.get_or_fetch_bundle(&tx_id.to_string()).await {
-                Ok(_) => {},
+                Ok(_) => {}
                 Err(e) => {
                     error!("failed to fetch transaction: {e}");
                 }
@@ -213,25 +596,337 @@ impl Cli {
             info!("transaction from cache: {}", tx);
 
             // If for some reason the bundle data file does not exist, fetch it
-            let path = self.get_bundle_path(tx_id);
+            let path = bundle_path(&self.args, tx_id);
             if !path.exists() {
                 warn!("bundle file not found, fetching: {}", tx_id);
 
-                let bundle = self.client.get_bundle_data(tx).await?;
-                file::save_serde_json(self.get_bundle_path(tx_id), &bundle.items)?;
+                let stream = self.client.stream_bundle_data(tx).await?;
+                save_bundle_stream(&self.args, tx_id, stream).await?;
             }
             return Ok(());
         }
 
-        let (tx, bundle) = self.client.get_bundle(tx_id.as_str()).await?;
+        let (tx, stream) = self.client.stream_bundle(tx_id.as_str()).await?;
         info!("transaction: {}", tx);
 
-        self.cache.data.insert(tx_id.clone(), tx.clone());
-        file::save_serde_json(self.get_bundle_path(tx_id), &bundle.items)?;
+        self.cache.data.insert(tx_id.clone(), tx);
+        save_bundle_stream(&self.args, tx_id, stream).await
+    }
+
+    /// Keeps the peer state for the next run, the cache writes it on drop.
+    fn save_peers(&mut self) {
+        if let Some(cache) = &mut self.peers_cache {
+            cache.data = self.client.peers().snapshot();
+        }
+    }
+}
+
+/// Fails a batch when any of its bundles failed.
+fn batch_result(failed: Vec<String>) -> Result<(), Error> {
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(Error::BatchError(failed.len())),
+    }
+}
+
+/// Progress of a crawl, per crawled height range.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct CrawlCheckpoint {
+    /// Next height to crawl.
+    height: u64,
+    /// Transactions and bundles that failed, retried when the crawl runs again.
+    failed: Vec<String>,
+}
+
+fn bundle_path(args: &Args, tx_id: &str) -> PathBuf {
+    Path::new(&args.out_dir).join(format!("{}.json", tx_id))
+}
+
+/// Output of an item fetched alone from its bundle.
+fn item_path(args: &Args, tx_id: &str, item_id: &str) -> PathBuf {
+    Path::new(&args.out_dir).join(format!("{tx_id}.{item_id}.json"))
+}
+
+/// Saves the items of a bundle as they are parsed. The output only appears
+/// once the bundle data is verified, so a failing bundle leaves none behind
+/// and is fetched again on the next run.
+async fn save_bundle_stream(
+    args: &Args,
+    tx_id: &str,
+    mut stream: VerifiedBundleStream,
+) -> Result<(), Error> {
+    let mut writer = BundleWriter::create(args, tx_id, bundle_path(args, tx_id))?;
+    while let Some(item) = stream.next_item().await {
+        writer.write_item(item?)?;
+    }
+
+    let diagnostics = stream.verify().await?;
+    writer.finish(&diagnostics)
+}
+
+/// Saves the items of a bundle already held in memory to `path`.
+fn save_bundle(args: &Args, tx_id: &str, path: PathBuf, bundle: Bundle) -> Result<(), Error> {
+    let mut writer = BundleWriter::create(args, tx_id, path)?;
+    for item in bundle.items {
+        writer.write_item(item)?;
+    }
+
+    writer.finish(&bundle.diagnostics)
+}
+
+/// Writes bundle items one at a time to a JSON file, and with `--extract`
+/// their data to `<out-dir>/<tx-id>/`. Items whose signature could not be
+/// verified are dropped.
+struct BundleWriter<'a> {
+    args: &'a Args,
+    tx_id: &'a str,
+    json: JsonArrayWriter,
+    extractor: Option<Extractor>,
+    dropped: usize,
+}
+
+impl<'a> BundleWriter<'a> {
+    fn create(args: &'a Args, tx_id: &'a str, path: PathBuf) -> Result<Self, Error> {
+        let extractor = match args.extract {
+            true => Some(Extractor::new(&Path::new(&args.out_dir).join(tx_id))?),
+            false => None,
+        };
+
+        if args.layout {
+            println!("layout of bundle {tx_id}:");
+        }
+
+        Ok(Self {
+            args,
+            tx_id,
+            json: JsonArrayWriter::create(path)?,
+            extractor,
+            dropped: 0,
+        })
+    }
+
+    fn write_item(&mut self, mut item: BundleItem) -> Result<(), Error> {
+        if !item.verified {
+            self.dropped += 1;
+            return Ok(());
+        }
+        self.dropped += item.retain_verified();
+
+        if self.args.layout {
+            print_layout(std::slice::from_ref(&item), 1);
+        }
+
+        if let Some(extractor) = &mut self.extractor {
+            extractor.extract(&item)?;
+        }
+
+        if self.args.decode_tags {
+            item.decode_tags(self.args.tags_fallback);
+        }
+
+        self.json.write(&item)?;
         Ok(())
     }
 
-    fn get_bundle_path(&self, tx_id: &String) -> PathBuf {
-        Path::new(&self.args.out_dir).join(format!("{}.json", tx_id))
+    fn finish(self, diagnostics: &[ItemDiagnostic]) -> Result<(), Error> {
+        for d in diagnostics {
+            let bundle_id = d.parent_id.as_deref().unwrap_or(self.tx_id);
+            // Items whose nested bundle failed to unpack are kept.
+            let failure = match d.error {
+                bundle::Error::NestedBundleError {
+                    ..
+                } => "nested unpack failed for",
+                _ => "skipped",
+            };
+            warn!(
+                "{failure} item {} {} at offset {} of {bundle_id}: {}",
+                d.index, d.id, d.offset, d.error
+            );
+        }
+
+        if self.dropped > 0 {
+            warn!("dropped {} items with invalid signatures from: {}", self.dropped, self.tx_id);
+        }
+
+        if let Some(extractor) = self.extractor {
+            let manifest = extractor.finish()?;
+            let dir = Path::new(&self.args.out_dir).join(self.tx_id);
+            info!("extracted {} items to: {}", manifest.len(), dir.display());
+        }
+
+        self.json.finish()?;
+        Ok(())
+    }
+}
+
+/// Prints one line per item, nested items indented under their parent.
+fn print_layout(items: &[BundleItem], depth: usize) {
+    for item in items {
+        if let Some(layout) = &item.layout {
+            println!("{:indent$}{} {layout}", "", item.id, indent = depth * 2);
+        }
+        print_layout(&item.items, depth + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::tests::bundle_tx;
+    use apache_avro::Schema;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const TEST_BUNDLE_TX: &str = "aJ3PrkyJ6GpdwwUxxXFHiB40cEg-GPRUWcKUI6wCgPQ";
+
+    fn test_args(out_dir: &Path, extra: &[&str]) -> Args {
+        let mut args = vec!["-o", out_dir.to_str().unwrap()];
+        args.extend(extra);
+        Args::from_args(&["axer"], &args).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_save_bundle_stream_data_root_mismatch() {
+        let test_bundle = std::fs::read("res/test_bundle").unwrap();
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/{TEST_BUNDLE_TX}")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(test_bundle.clone()))
+            .mount(&server)
+            .await;
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("axer-save-{}", std::process::id()));
+        let args = test_args(&dir, &["--extract"]);
+
+        let mut tampered = test_bundle.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        let stream =
+            client.stream_bundle_data(&bundle_tx(TEST_BUNDLE_TX, &tampered)).await.unwrap();
+        let result = save_bundle_stream(&args, TEST_BUNDLE_TX, stream).await;
+        assert!(matches!(result, Err(Error::ClientError(client::Error::DataRootMismatch { .. }))));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        let stream =
+            client.stream_bundle_data(&bundle_tx(TEST_BUNDLE_TX, &test_bundle)).await.unwrap();
+        save_bundle_stream(&args, TEST_BUNDLE_TX, stream).await.unwrap();
+        assert!(bundle_path(&args, TEST_BUNDLE_TX).exists());
+        assert_eq!(std::fs::read_dir(dir.join(TEST_BUNDLE_TX)).unwrap().count(), 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_bundle_item_extract() {
+        let test_bundle = std::fs::read("res/test_bundle").unwrap();
+        let schema = Schema::parse_str(client::TAGS_AVRO_SCHEMA).unwrap();
+        let mut bundle = Bundle::new(&test_bundle, &schema).unwrap();
+        let item = bundle.items.remove(1);
+        let item_id = item.id.clone();
+        bundle.items = vec![item];
+
+        let dir = std::env::temp_dir().join(format!("axer-item-{}", std::process::id()));
+        let args = test_args(&dir, &["--extract"]);
+        let path = item_path(&args, TEST_BUNDLE_TX, &item_id);
+        save_bundle(&args, TEST_BUNDLE_TX, path.clone(), bundle).unwrap();
+
+        // Extracted like an item of the whole bundle.
+        assert!(path.exists());
+        let files: Vec<String> = std::fs::read_dir(dir.join(TEST_BUNDLE_TX))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(files.len(), 2);
+        assert!(files.iter().any(|file| file.starts_with(&format!("{item_id}."))));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn block(height: u64, txs: &[&str]) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "indep_hash": format!("block-{height}"),
+            "height": height,
+            "timestamp": 0,
+            "txs": txs,
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_crawl_resume() {
+        let dir = std::env::temp_dir().join(format!("axer-crawl-{}", std::process::id()));
+        let checkpoint_file = dir.join("crawl.json").to_str().unwrap().to_string();
+        let db_file = dir.join("cache.json").to_str().unwrap().to_string();
+
+        let server = MockServer::start().await;
+        let args = test_args(
+            &dir,
+            &[
+                "--url",
+                &server.uri(),
+                "--crawl",
+                "--min-height",
+                "1",
+                "--max-height",
+                "3",
+                "--max-attempts",
+                "1",
+                "--db-file",
+                &db_file,
+                "--checkpoint-file",
+                &checkpoint_file,
+            ],
+        );
+
+        // The transaction of block 1 is missing, and block 3 cannot be fetched.
+        for (height, response) in [(1, block(1, &["missing"])), (2, block(2, &[]))] {
+            Mock::given(method("GET"))
+                .and(path(format!("/block/height/{height}")))
+                .respond_with(response)
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("GET"))
+            .and(path("/block/height/3"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let mut cli = Cli::new(args.clone()).unwrap();
+        assert!(cli.handle_crawl_mode().await.is_err());
+        drop(cli);
+
+        let checkpoint = |key: &str| {
+            let checkpoints = Cache::<CrawlCheckpoint>::from_file(checkpoint_file.clone()).unwrap();
+            checkpoints.data[key].clone()
+        };
+        assert_eq!(checkpoint("1-3").height, 3);
+        assert_eq!(checkpoint("1-3").failed, ["missing"]);
+
+        // The crawl resumes at the block it stopped at, retrying the failed
+        // transaction, which turns out not to be a bundle.
+        server.verify().await;
+        server.reset().await;
+        Mock::given(method("GET"))
+            .and(path("/block/height/3"))
+            .respond_with(block(3, &[]))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/tx/missing"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(bundle_tx("missing", b"")))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut cli = Cli::new(args).unwrap();
+        cli.handle_crawl_mode().await.unwrap();
+        drop(cli);
+
+        assert_eq!(checkpoint("1-3").height, 4);
+        assert!(checkpoint("1-3").failed.is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
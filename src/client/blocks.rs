@@ -0,0 +1,103 @@
+use crate::bundle::tx::BundleTx;
+use crate::client::{Client, Error};
+use futures::StreamExt;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Block {
+    pub indep_hash: String,
+    pub height: u64,
+    pub timestamp: u64,
+    pub txs: Vec<String>,
+}
+
+/// Valid bundles of a block, and the transactions that could not be fetched.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockBundles {
+    pub bundles: Vec<BundleTx>,
+    pub failed: Vec<String>,
+}
+
+impl Client {
+    pub async fn get_block(&self, height: u64) -> Result<Block, Error> {
+        let response = self.get(&format!("/block/height/{}", height)).await?;
+
+        match response.status() {
+            StatusCode::OK => Ok(response.json::<Block>().await.map_err(Error::from)?),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Requests every transaction of the block at the height, keeping the
+    /// valid bundles in block order. A transaction that fails to fetch is
+    /// listed as failed, only a failing block request fails the call.
+    pub async fn get_block_bundles(&self, height: u64) -> Result<BlockBundles, Error> {
+        let block = self.get_block(height).await?;
+
+        let results: Vec<(&String, Result<BundleTx, Error>)> = futures::stream::iter(&block.txs)
+            .map(|id| async move { (id, self.limited(self.get_tx(id)).await) })
+            .buffered(self.concurrency)
+            .collect()
+            .await;
+
+        let mut bundles = BlockBundles::default();
+        for (id, result) in results {
+            match result.map(|tx| (tx.is_valid(), tx)) {
+                Ok((Ok(()), tx)) => bundles.bundles.push(tx),
+                Ok((Err(e), _)) => debug!("skipping transaction {id} of block {height}: {e}"),
+                Err(e) => {
+                    warn!("failed to fetch transaction {id} of block {height}: {e}");
+                    bundles.failed.push(id.clone());
+                }
+            }
+        }
+
+        Ok(bundles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::builder::Signer;
+    use crate::bundle::tx::tests::signed_tx;
+    use crate::client::DEFAULT_TIMEOUT_MS;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_get_block_bundles() {
+        let signer = Signer::from_keyfile("res/test_wallet.json").unwrap();
+        let bundle = signed_tx(&signer);
+        let mut forged = signed_tx(&signer);
+        forged.reward = "1".to_string();
+        let mut plain = signed_tx(&signer);
+        plain.tags.0.clear();
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/block/height/1000"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "indep_hash": "hash",
+                "height": 1000,
+                "timestamp": 1700000000,
+                "txs": [&plain.id, &bundle.id, "missing", &forged.id],
+            })))
+            .mount(&server)
+            .await;
+        for tx in [&bundle, &forged, &plain] {
+            Mock::given(method("GET"))
+                .and(path(format!("/tx/{}", tx.id)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(tx))
+                .mount(&server)
+                .await;
+        }
+
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap();
+        let block = client.get_block_bundles(1000).await.unwrap();
+        assert_eq!(block.bundles, [bundle]);
+        assert_eq!(block.failed, ["missing"]);
+    }
+}
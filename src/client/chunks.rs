@@ -0,0 +1,209 @@
+use crate::bundle;
+use crate::bundle::merkle::{chunk_ranges, validate_path};
+use crate::bundle::tx::BundleTx;
+use crate::client::{BodyReader, Client, Error};
+use crate::utils;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
+use base64::Engine;
+use bytes::Bytes;
+use futures::{StreamExt, TryStreamExt};
+use reqwest::StatusCode;
+use serde::{Deserialize, Deserializer};
+use std::io;
+use std::str::FromStr;
+use tokio_util::io::StreamReader;
+
+/// Location of a transaction's data in the weave.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TxOffset {
+    /// Absolute offset of the last byte of the data.
+    #[serde(deserialize_with = "from_str")]
+    pub offset: u64,
+    #[serde(deserialize_with = "from_str")]
+    pub size: u64,
+}
+
+impl TxOffset {
+    /// Absolute offset of the first byte of the data.
+    pub fn start(&self) -> Result<u64, Error> {
+        self.offset.checked_add(1).and_then(|end| end.checked_sub(self.size)).ok_or(
+            Error::InvalidTxOffset {
+                offset: self.offset,
+                size: self.size,
+            },
+        )
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct Chunk {
+    pub chunk: String,
+    pub data_path: String,
+    pub tx_path: Option<String>,
+}
+
+fn from_str<'de, D: Deserializer<'de>, T: FromStr>(deserializer: D) -> Result<T, D::Error>
+where
+    T::Err: std::fmt::Display,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(serde::de::Error::custom)
+}
+
+impl Client {
+    pub async fn get_tx_offset(&self, id: &str) -> Result<TxOffset, Error> {
+        let response = self.get_with_peers(&format!("/tx/{}/offset", id)).await?;
+
+        match response.status() {
+            StatusCode::OK => Ok(response.json::<TxOffset>().await.map_err(Error::from)?),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Requests the chunk holding the given absolute weave offset.
+    pub async fn get_chunk(&self, offset: u64) -> Result<Chunk, Error> {
+        let response = self.get_with_peers(&format!("/chunk/{}", offset)).await?;
+
+        match response.status() {
+            StatusCode::OK => Ok(response.json::<Chunk>().await.map_err(Error::from)?),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Downloads the data of a transaction chunk by chunk, straight from the
+    /// weave instead of the gateway data endpoint.
+    /// Chunks are requested concurrently and read back in order, each one
+    /// checked against its merkle proof under the transaction data root.
+    pub async fn get_chunked_data(&self, tx: &BundleTx) -> Result<BodyReader, Error> {
+        let size = tx.data_size_bytes()?;
+        let data_root = tx.data_root_bytes()?;
+
+        let tx_offset = self.get_tx_offset(&tx.id).await?;
+        if tx_offset.size != size as u64 {
+            return Err(Error::DataSizeMismatch {
+                expected: size as u64,
+                found: tx_offset.size,
+            });
+        }
+        let start = tx_offset.start()?;
+
+        let client = self.clone();
+        let concurrency = self.concurrency;
+        let ranges = chunk_ranges(size).into_iter().filter(|range| !range.is_empty());
+        let chunks = futures::stream::iter(ranges)
+            .map(move |range| {
+                let client = client.clone();
+                let data_root = data_root.clone();
+                async move {
+                    let offset =
+                        start.checked_add(range.start as u64).ok_or(Error::InvalidTxOffset {
+                            offset: start,
+                            size: size as u64,
+                        })?;
+                    let chunk = client.limited(client.get_chunk(offset)).await?;
+                    let data = base64.decode(&chunk.chunk).map_err(bundle::Error::from)?;
+                    let data_path = base64.decode(&chunk.data_path).map_err(bundle::Error::from)?;
+
+                    match validate_path(&data_root, range.start, size, &data_path) {
+                        Some(proven)
+                            if proven.range == range
+                                && proven.data_hash == utils::sha256(&data) =>
+                        {
+                            Ok(Bytes::from(data))
+                        }
+                        _ => Err(Error::InvalidChunkProof {
+                            offset,
+                        }),
+                    }
+                }
+            })
+            .buffered(concurrency)
+            .map_err(io::Error::other)
+            .boxed();
+
+        Ok(StreamReader::new(chunks))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::bundle::merkle::{generate_proofs, MAX_CHUNK_SIZE};
+    use crate::bundle::Bundle;
+    use crate::client::tests::bundle_tx;
+    use crate::client::{DEFAULT_TIMEOUT_MS, TAGS_AVRO_SCHEMA};
+    use apache_avro::Schema;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    pub(crate) async fn mount_chunks(
+        server: &MockServer,
+        id: &str,
+        data: &[u8],
+        data_paths: &[Vec<u8>],
+    ) {
+        let end_offset = 1_000_000 + data.len() as u64 - 1;
+        Mock::given(method("GET"))
+            .and(path(format!("/tx/{id}/offset")))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "offset": end_offset.to_string(),
+                "size": data.len().to_string(),
+            })))
+            .mount(server)
+            .await;
+
+        for (range, data_path) in chunk_ranges(data.len()).iter().zip(data_paths) {
+            Mock::given(method("GET"))
+                .and(path(format!("/chunk/{}", 1_000_000 + range.start)))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "chunk": base64.encode(&data[range.start..range.end]),
+                    "data_path": base64.encode(data_path),
+                })))
+                .mount(server)
+                .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_bundle_data_chunked() {
+        let test_bundle = std::fs::read("res/test_bundle").unwrap();
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let expected = Bundle::new(&test_bundle, &schema).unwrap();
+
+        // Pad the bundle data into a few chunks, the trailing zeros are ignored
+        // by the parser as they are past the last item.
+        let mut data = test_bundle.clone();
+        data.resize(2 * MAX_CHUNK_SIZE + 1000, 0);
+
+        let server = MockServer::start().await;
+        mount_chunks(&server, "bundle", &data, &generate_proofs(&data)).await;
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap().with_chunked_data(true);
+
+        let bundle = client.get_bundle_data(&bundle_tx("bundle", &data)).await.unwrap();
+        assert!(bundle.items == expected.items);
+
+        // Proofs of other data do not match the chunks nor the data root.
+        let mut forged = data.clone();
+        forged[0] ^= 1;
+        let result = client.get_bundle_data(&bundle_tx("bundle", &forged)).await;
+        assert!(result.is_err());
+
+        let server = MockServer::start().await;
+        mount_chunks(&server, "forged", &forged, &generate_proofs(&data)).await;
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap().with_chunked_data(true);
+
+        let result = client.get_bundle_data(&bundle_tx("forged", &data)).await;
+        assert!(result.is_err_and(|e| e.to_string().contains("invalid merkle proof")));
+    }
+
+    #[test]
+    fn test_tx_offset_start() {
+        let offset = |offset, size| TxOffset {
+            offset,
+            size,
+        };
+        assert_eq!(offset(1_000_999, 1000).start().unwrap(), 1_000_000);
+        assert!(matches!(offset(u64::MAX, 1).start(), Err(Error::InvalidTxOffset { .. })));
+        assert!(matches!(offset(10, 12).start(), Err(Error::InvalidTxOffset { .. })));
+    }
+}
@@ -0,0 +1,208 @@
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use url::Url;
+
+/// Consecutive failures after which a gateway is avoided for a while.
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+const FAILURE_COOLDOWN: Duration = Duration::from_secs(30);
+
+/// Weight of the newest sample in the latency moving average.
+const LATENCY_SMOOTHING: f64 = 0.3;
+
+/// How the next gateway is picked among the healthy ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum GatewayStrategy {
+    #[default]
+    RoundRobin,
+    /// Gateways without latency samples yet are tried first.
+    LowestLatency,
+}
+
+impl FromStr for GatewayStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "round-robin" => Ok(GatewayStrategy::RoundRobin),
+            "lowest-latency" => Ok(GatewayStrategy::LowestLatency),
+            _ => Err(format!(
+                "unknown gateway strategy: {s}, expected round-robin or lowest-latency"
+            )),
+        }
+    }
+}
+
+impl Display for GatewayStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GatewayStrategy::RoundRobin => write!(f, "round-robin"),
+            GatewayStrategy::LowestLatency => write!(f, "lowest-latency"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GatewayHealth {
+    /// Moving average of the time to the response headers.
+    pub latency: Option<Duration>,
+    /// Failures since the last success.
+    pub failures: u32,
+    pub last_failure: Option<Instant>,
+}
+
+impl GatewayHealth {
+    pub fn is_healthy(&self) -> bool {
+        match self.last_failure {
+            Some(at) if self.failures >= MAX_CONSECUTIVE_FAILURES => {
+                at.elapsed() >= FAILURE_COOLDOWN
+            }
+            _ => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PoolState {
+    health: Vec<GatewayHealth>,
+    next: usize,
+}
+
+/// Gateways and nodes requests are spread over, with their health shared
+/// across clones of the pool.
+#[derive(Debug, Clone)]
+pub struct GatewayPool {
+    gateways: Vec<Url>,
+    strategy: GatewayStrategy,
+    state: Arc<Mutex<PoolState>>,
+}
+
+impl GatewayPool {
+    pub fn new(gateways: Vec<Url>, strategy: GatewayStrategy) -> Self {
+        let state = PoolState {
+            health: vec![GatewayHealth::default(); gateways.len()],
+            next: 0,
+        };
+
+        Self {
+            gateways,
+            strategy,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    pub fn gateways(&self) -> &[Url] {
+        &self.gateways
+    }
+
+    pub fn len(&self) -> usize {
+        self.gateways.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.gateways.is_empty()
+    }
+
+    pub fn url(&self, index: usize) -> &Url {
+        &self.gateways[index]
+    }
+
+    pub fn health(&self) -> Vec<GatewayHealth> {
+        self.state.lock().unwrap().health.clone()
+    }
+
+    /// Picks the next gateway for a request, skipping the `tried` ones.
+    /// Unhealthy gateways are only picked when no healthy one is left.
+    pub fn select(&self, tried: &[usize]) -> Option<usize> {
+        let mut state = self.state.lock().unwrap();
+        let untried = |index: &usize| !tried.contains(index);
+
+        // Candidates in round robin order, starting at the next gateway.
+        let candidates: Vec<usize> = (0..self.gateways.len())
+            .map(|i| (state.next + i) % self.gateways.len())
+            .filter(untried)
+            .collect();
+        let healthy: Vec<usize> =
+            candidates.iter().copied().filter(|&i| state.health[i].is_healthy()).collect();
+        let candidates = match healthy.is_empty() {
+            true => candidates,
+            false => healthy,
+        };
+
+        let index = match self.strategy {
+            GatewayStrategy::RoundRobin => candidates.first().copied(),
+            GatewayStrategy::LowestLatency => {
+                candidates.into_iter().min_by_key(|&i| state.health[i].latency.unwrap_or_default())
+            }
+        }?;
+
+        state.next = (index + 1) % self.gateways.len();
+        Some(index)
+    }
+
+    pub fn record_success(&self, index: usize, latency: Duration) {
+        let health = &mut self.state.lock().unwrap().health[index];
+
+        health.failures = 0;
+        health.latency = Some(match health.latency {
+            Some(average) => {
+                average.mul_f64(1.0 - LATENCY_SMOOTHING) + latency.mul_f64(LATENCY_SMOOTHING)
+            }
+            None => latency,
+        });
+    }
+
+    pub fn record_failure(&self, index: usize) {
+        let health = &mut self.state.lock().unwrap().health[index];
+
+        health.failures += 1;
+        health.last_failure = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_pool(strategy: GatewayStrategy) -> GatewayPool {
+        let gateways = ["http://a.test", "http://b.test", "http://c.test"]
+            .iter()
+            .map(|url| Url::parse(url).unwrap())
+            .collect();
+        GatewayPool::new(gateways, strategy)
+    }
+
+    #[test]
+    fn test_gateway_selection() {
+        let pool = test_pool(GatewayStrategy::RoundRobin);
+        assert_eq!(pool.select(&[]), Some(0));
+        assert_eq!(pool.select(&[]), Some(1));
+        assert_eq!(pool.select(&[2]), Some(0));
+        assert_eq!(pool.select(&[0, 1, 2]), None);
+
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            pool.record_failure(1);
+        }
+        assert_eq!(pool.select(&[]), Some(2));
+        assert_eq!(pool.select(&[]), Some(0));
+        assert_eq!(pool.select(&[]), Some(2));
+        // An unhealthy gateway is still used as the last resort.
+        assert_eq!(pool.select(&[0, 2]), Some(1));
+
+        pool.record_success(1, Duration::from_millis(10));
+        assert!(pool.health()[1].is_healthy());
+
+        let pool = test_pool(GatewayStrategy::LowestLatency);
+        pool.record_success(0, Duration::from_millis(300));
+        pool.record_success(1, Duration::from_millis(100));
+        pool.record_success(2, Duration::from_millis(200));
+        assert_eq!(pool.select(&[]), Some(1));
+        assert_eq!(pool.select(&[1]), Some(2));
+
+        pool.record_success(1, Duration::from_millis(1000));
+        let latency = pool.health()[1].latency.unwrap().as_secs_f64();
+        assert!((latency - 0.37).abs() < 1e-6);
+        assert_eq!(pool.select(&[]), Some(2));
+    }
+}
@@ -0,0 +1,409 @@
+use crate::bundle::tags::Tag;
+use crate::bundle::tx::{BUNDLE_FORMAT_SUPPORTED, BUNDLE_VERSION_SUPPORTED};
+use crate::client::{Client, Error};
+use bytes::Bytes;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Largest page the gateways serve.
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+const TRANSACTIONS_QUERY: &str = r#"
+query($owners: [String!], $tags: [TagFilter!], $block: BlockFilter, $bundledIn: [ID!], $first: Int, $after: String) {
+	transactions(owners: $owners, tags: $tags, block: $block, bundledIn: $bundledIn, first: $first, after: $after) {
+		pageInfo { hasNextPage }
+		edges {
+			cursor
+			node {
+				id
+				owner { address }
+				data { size type }
+				tags { name value }
+				block { height timestamp }
+				bundledIn { id }
+			}
+		}
+	}
+}"#;
+
+/// Matches transactions having the tag with any of the values.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TagFilter {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+impl FromStr for TagFilter {
+    type Err = String;
+
+    /// Parses `name=value`, or `name=value1,value2` for several values.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((name, values)) if !name.is_empty() => Ok(TagFilter {
+                name: name.to_string(),
+                values: values.split(',').map(String::from).collect(),
+            }),
+            _ => Err(format!("invalid tag filter: {s}, expected name=value")),
+        }
+    }
+}
+
+/// Inclusive block height range.
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct BlockFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<u64>,
+}
+
+/// Filters and page of a `transactions` query, all filters must match.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TransactionQuery {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub owners: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<TagFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block: Option<BlockFilter>,
+    /// `Some(None)` is sent as null, which keeps base layer transactions only.
+    #[serde(rename = "bundledIn", skip_serializing_if = "Option::is_none")]
+    pub bundled_in: Option<Option<Vec<String>>>,
+    pub first: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+impl Default for TransactionQuery {
+    fn default() -> Self {
+        Self {
+            owners: vec![],
+            tags: vec![],
+            block: None,
+            bundled_in: None,
+            first: MAX_PAGE_SIZE,
+            after: None,
+        }
+    }
+}
+
+impl TransactionQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Transactions tagged as ANS-104 bundles this crate can parse. Nested
+    /// bundles carry the same tags, but are data items fetched through their
+    /// parent, so they are left out.
+    pub fn bundles() -> Self {
+        Self::new()
+            .tag("Bundle-Format", BUNDLE_FORMAT_SUPPORTED)
+            .tag("Bundle-Version", BUNDLE_VERSION_SUPPORTED)
+            .base_layer()
+    }
+
+    /// Leaves out data items, keeping transactions posted to the base layer.
+    pub fn base_layer(mut self) -> Self {
+        self.bundled_in = Some(None);
+        self
+    }
+
+    pub fn owner(mut self, address: &str) -> Self {
+        self.owners.push(address.to_string());
+        self
+    }
+
+    pub fn tag(self, name: &str, value: &str) -> Self {
+        self.tag_filter(TagFilter {
+            name: name.to_string(),
+            values: vec![value.to_string()],
+        })
+    }
+
+    pub fn tag_filter(mut self, filter: TagFilter) -> Self {
+        self.tags.push(filter);
+        self
+    }
+
+    pub fn heights(mut self, min: Option<u64>, max: Option<u64>) -> Self {
+        self.block = Some(BlockFilter {
+            min,
+            max,
+        });
+        self
+    }
+
+    pub fn first(mut self, first: u32) -> Self {
+        self.first = first.min(MAX_PAGE_SIZE);
+        self
+    }
+}
+
+#[derive(Serialize)]
+struct GraphqlRequest<'a> {
+    query: &'a str,
+    variables: &'a TransactionQuery,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphqlResponse {
+    data: Option<TransactionsData>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GraphqlError {
+    message: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct TransactionsData {
+    transactions: TransactionPage,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PageInfo {
+    pub has_next_page: bool,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionPage {
+    pub page_info: PageInfo,
+    pub edges: Vec<TransactionEdge>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct TransactionEdge {
+    pub cursor: String,
+    pub node: TransactionNode,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionNode {
+    pub id: String,
+    pub owner: Owner,
+    pub data: DataInfo,
+    pub tags: Vec<Tag<String>>,
+    /// Missing while the transaction is pending.
+    pub block: Option<BlockInfo>,
+    /// Set for data items, to their parent bundle.
+    pub bundled_in: Option<BundledIn>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Owner {
+    pub address: String,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DataInfo {
+    pub size: String,
+    #[serde(rename = "type")]
+    pub content_type: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockInfo {
+    pub height: u64,
+    pub timestamp: u64,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct BundledIn {
+    pub id: String,
+}
+
+impl Client {
+    /// Requests one page of transactions from the gateway `/graphql` endpoint.
+    pub async fn query_transactions(
+        &self,
+        query: &TransactionQuery,
+    ) -> Result<TransactionPage, Error> {
+        let body = serde_json::to_vec(&GraphqlRequest {
+            query: TRANSACTIONS_QUERY,
+            variables: query,
+        })
+        .map_err(|e| Error::GraphqlError(e.to_string()))?;
+        let headers =
+            HeaderMap::from_iter([(CONTENT_TYPE, HeaderValue::from_static("application/json"))]);
+
+        // Queries have no side effects, so they are retried like GETs.
+        let response =
+            self.send(Method::POST, "/graphql", headers, Some(Bytes::from(body))).await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let response = response.json::<GraphqlResponse>().await.map_err(Error::from)?;
+                if let Some(error) = response.errors.first() {
+                    return Err(Error::GraphqlError(error.message.clone()));
+                }
+
+                match response.data {
+                    Some(data) => Ok(data.transactions),
+                    None => Err(Error::GraphqlError("response without data".to_string())),
+                }
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Follows the query cursor page by page until `limit` transactions are
+    /// found or there are no more pages.
+    pub async fn query_all_transactions(
+        &self,
+        query: &TransactionQuery,
+        limit: usize,
+    ) -> Result<Vec<TransactionNode>, Error> {
+        let mut query = query.clone();
+        let mut nodes = vec![];
+
+        // Not every gateway applies the filter, but they all set `bundledIn`.
+        let base_layer = query.bundled_in == Some(None);
+
+        while nodes.len() < limit {
+            let page = self.query_transactions(&query).await?;
+            query.after = page.edges.last().map(|edge| edge.cursor.clone());
+            nodes.extend(
+                page.edges
+                    .into_iter()
+                    .map(|edge| edge.node)
+                    .filter(|node| !base_layer || node.bundled_in.is_none()),
+            );
+
+            if !page.page_info.has_next_page || query.after.is_none() {
+                break;
+            }
+        }
+
+        nodes.truncate(limit);
+        Ok(nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::DEFAULT_TIMEOUT_MS;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn page(ids: &[&str], has_next_page: bool) -> serde_json::Value {
+        let edges: Vec<serde_json::Value> = ids
+            .iter()
+            .map(|id| {
+                serde_json::json!({
+                    "cursor": format!("cursor-{id}"),
+                    "node": {
+                        "id": id,
+                        "owner": { "address": "owner" },
+                        "data": { "size": "1024", "type": null },
+                        "tags": [{ "name": "Bundle-Format", "value": "binary" }],
+                        "block": { "height": 1000, "timestamp": 1700000000 },
+                        "bundledIn": null,
+                    },
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "data": {
+                "transactions": {
+                    "pageInfo": { "hasNextPage": has_next_page },
+                    "edges": edges,
+                },
+            },
+        })
+    }
+
+    #[test]
+    fn test_tag_filter_from_str() {
+        let filter: TagFilter = "App-Name=a,b".parse().unwrap();
+        assert_eq!(filter.name, "App-Name");
+        assert_eq!(filter.values, ["a", "b"]);
+        assert!("=value".parse::<TagFilter>().is_err());
+        assert!("name".parse::<TagFilter>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_query_all_transactions() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(serde_json::json!({ "variables": { "after": "cursor-b" } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page(&["c"], false)))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(serde_json::json!({
+                "variables": {
+                    "owners": ["owner"],
+                    "tags": [
+                        { "name": "Bundle-Format", "values": ["binary"] },
+                        { "name": "Bundle-Version", "values": ["2.0.0"] },
+                    ],
+                    "block": { "min": 1000, "max": 2000 },
+                    "first": 2,
+                },
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(page(&["a", "b"], true)))
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap();
+        let query =
+            TransactionQuery::bundles().owner("owner").heights(Some(1000), Some(2000)).first(2);
+
+        let nodes = client.query_all_transactions(&query, 10).await.unwrap();
+        let ids: Vec<&str> = nodes.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+        assert_eq!(nodes[0].block.as_ref().unwrap().height, 1000);
+
+        let nodes = client.query_all_transactions(&query, 1).await.unwrap();
+        assert_eq!(nodes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_query_bundles_base_layer() {
+        let mut body = page(&["a", "item"], false);
+        body["data"]["transactions"]["edges"][1]["node"]["bundledIn"] =
+            serde_json::json!({ "id": "a" });
+
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .and(body_partial_json(serde_json::json!({ "variables": { "bundledIn": null } })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(body))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap();
+        let nodes = client.query_all_transactions(&TransactionQuery::bundles(), 10).await.unwrap();
+        let ids: Vec<&str> = nodes.iter().map(|node| node.id.as_str()).collect();
+        assert_eq!(ids, ["a"]);
+    }
+
+    #[tokio::test]
+    async fn test_query_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/graphql"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "data": null,
+                "errors": [{ "message": "invalid query" }],
+            })))
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap();
+        let result = client.query_transactions(&TransactionQuery::new()).await;
+        assert!(matches!(result, Err(Error::GraphqlError(message)) if message == "invalid query"));
+    }
+}
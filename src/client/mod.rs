@@ -0,0 +1,599 @@
+use crate::bundle;
+use crate::bundle::merkle::ChunkHashingReader;
+use crate::bundle::network::Network;
+use crate::bundle::stream::BundleStream;
+use crate::bundle::tx::BundleTx;
+use crate::bundle::{Bundle, BundleItem, ItemDiagnostic, ItemHeader, ParseOptions};
+use crate::client::gateways::{GatewayPool, GatewayStrategy};
+use crate::client::peers::PeerPool;
+use crate::client::rate_limit::RateLimiter;
+use crate::client::retry::RetryPolicy;
+use apache_avro::Schema;
+use base64::prelude::BASE64_URL_SAFE_NO_PAD as base64;
+use base64::Engine;
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, RANGE};
+use reqwest::{Method, Response, StatusCode};
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio_util::io::StreamReader;
+use tracing::warn;
+
+pub mod blocks;
+pub mod chunks;
+pub mod gateways;
+pub mod graphql;
+pub mod peers;
+pub mod rate_limit;
+pub mod retry;
+
+pub const DEFAULT_BASE_URL: &str = "https://arweave.net";
+pub const DEFAULT_TIMEOUT_MS: u64 = 5000;
+/// Requests sent at the same time for the chunks of a download or the
+/// transactions of a block.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("request error: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("status error: {status} {message}")]
+    StatusError {
+        status: StatusCode,
+        message: String,
+    },
+    #[error("url error: {0}")]
+    UrlError(#[from] url::ParseError),
+    #[error("avro schema error: {0}")]
+    SchemaError(#[from] apache_avro::Error),
+    #[error("bundle error: {0}")]
+    BundleError(#[from] bundle::Error),
+    #[error("bundletx error: {0}")]
+    BundleTxError(#[from] bundle::tx::Error),
+    #[error("item {item_id} not found in bundle {bundle_id}")]
+    ItemNotFound {
+        bundle_id: String,
+        item_id: String,
+    },
+    #[error("data size mismatch, expected {expected} but found {found}")]
+    DataSizeMismatch {
+        expected: u64,
+        found: u64,
+    },
+    #[error("invalid tx offset {offset} for data size {size}")]
+    InvalidTxOffset {
+        offset: u64,
+        size: u64,
+    },
+    #[error("invalid merkle proof for chunk at offset {offset}")]
+    InvalidChunkProof {
+        offset: u64,
+    },
+    #[error("data root mismatch, expected {expected} but found {found}")]
+    DataRootMismatch {
+        expected: String,
+        found: String,
+    },
+    #[error("graphql error: {0}")]
+    GraphqlError(String),
+    #[error("no gateways configured")]
+    NoGateways,
+    #[error("range requests are not supported by the gateway")]
+    RangeNotSupported,
+    #[error("range response length mismatch, expected {expected} but found {found}")]
+    RangeLengthMismatch {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl Error {
+    /// Status error of an unexpected response. The body is kept as the
+    /// message when it can be read, as gateways explain errors there.
+    pub async fn from_response(response: Response) -> Self {
+        let status = response.status();
+        let message = match response.text().await {
+            Ok(message) => message,
+            Err(e) => format!("unreadable body: {e}"),
+        };
+
+        Error::StatusError {
+            status,
+            message,
+        }
+    }
+}
+
+pub const TAGS_AVRO_SCHEMA: &str = r#"
+{
+	"type": "array",
+	"items": {
+		"type": "record",
+		"name": "Tag",
+		"fields": [
+			{ "name": "name", "type": "bytes" },
+			{ "name": "value", "type": "bytes" }
+		]
+	}
+}"#;
+
+/// Async reader over a response body.
+pub type BodyReader = StreamReader<BoxStream<'static, io::Result<Bytes>>, Bytes>;
+
+/// Items of a bundle parsed as its data arrives, with the data hashed into
+/// its merkle root along the way.
+/// Items are not verified until `verify` succeeds, so anything built from
+/// them must be discarded when it fails.
+pub struct VerifiedBundleStream {
+    stream: BundleStream<ChunkHashingReader<BodyReader>>,
+    data_root: String,
+    expected: Vec<u8>,
+}
+
+impl VerifiedBundleStream {
+    pub async fn next_item(&mut self) -> Option<Result<BundleItem, Error>> {
+        Some(self.stream.next_item().await?.map_err(Error::from))
+    }
+
+    /// Reads whatever follows the last item and checks the data root,
+    /// returning the diagnostics of the skipped items.
+    pub async fn verify(self) -> Result<Vec<ItemDiagnostic>, Error> {
+        let (mut reader, diagnostics) = self.stream.into_parts();
+
+        // Bytes past the last item are part of the data root too.
+        tokio::io::copy(&mut reader, &mut tokio::io::sink()).await.map_err(bundle::Error::from)?;
+
+        match reader.finalize() {
+            Some(root) if root[..] == self.expected[..] => Ok(diagnostics),
+            root => Err(Error::DataRootMismatch {
+                expected: self.data_root,
+                found: root.map(|root| base64.encode(root)).unwrap_or_default(),
+            }),
+        }
+    }
+
+    /// Consumes every item into a bundle, once the data root checks out.
+    pub async fn into_bundle(mut self) -> Result<Bundle, Error> {
+        let mut items = vec![];
+        while let Some(item) = self.next_item().await {
+            items.push(item?);
+        }
+
+        let diagnostics = self.verify().await?;
+        Ok(Bundle {
+            items,
+            diagnostics,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Client {
+    client: reqwest::Client,
+    gateways: GatewayPool,
+    tags_schema: Schema,
+    parse_options: ParseOptions,
+    chunked: bool,
+    retry_policy: RetryPolicy,
+    peers: PeerPool,
+    rate_limiter: Option<RateLimiter>,
+    concurrency: usize,
+    /// Slots for fan-out requests, shared by every clone so concurrent
+    /// downloads split the same budget.
+    requests: Arc<Semaphore>,
+}
+
+impl Client {
+    pub fn new(base_url: String, timeout: u64) -> Result<Self, Error> {
+        let client = reqwest::Client::builder().timeout(Duration::from_millis(timeout)).build()?;
+        let base_url = url::Url::parse(base_url.as_str())?;
+
+        Ok(Self {
+            client,
+            gateways: GatewayPool::new(vec![base_url], GatewayStrategy::default()),
+            tags_schema: Schema::parse_str(TAGS_AVRO_SCHEMA)?,
+            parse_options: ParseOptions::default(),
+            chunked: false,
+            retry_policy: RetryPolicy::default(),
+            peers: PeerPool::default(),
+            rate_limiter: None,
+            concurrency: DEFAULT_CONCURRENCY,
+            requests: Arc::new(Semaphore::new(DEFAULT_CONCURRENCY)),
+        })
+    }
+
+    pub fn with_parse_options(mut self, parse_options: ParseOptions) -> Self {
+        self.parse_options = parse_options;
+        self
+    }
+
+    /// Download bundle data through the chunk endpoints instead of the
+    /// gateway data endpoint.
+    pub fn with_chunked_data(mut self, chunked: bool) -> Self {
+        self.chunked = chunked;
+        self
+    }
+
+    /// Spreads requests over the pool instead of the single base url.
+    pub fn with_gateways(mut self, gateways: GatewayPool) -> Self {
+        self.gateways = gateways;
+        self
+    }
+
+    /// Peers that data and chunk requests fall back to when the gateways miss
+    /// the data.
+    pub fn with_peers(mut self, peers: PeerPool) -> Self {
+        self.peers = peers;
+        self
+    }
+
+    pub fn peers(&self) -> &PeerPool {
+        &self.peers
+    }
+
+    /// Every request sent, retries and failovers included, waits for the
+    /// limiter.
+    pub fn with_rate_limiter(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Bounds the chunk and block transaction requests in flight over every
+    /// clone of the client.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self.requests = Arc::new(Semaphore::new(self.concurrency));
+        self
+    }
+
+    /// Sends a fan-out request once one of the shared slots is free.
+    pub(crate) async fn limited<T>(
+        &self,
+        request: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        // The semaphore is never closed, so the permit is always acquired.
+        let _permit = self.requests.acquire().await;
+        request.await
+    }
+
+    pub async fn get_network_info(&self) -> Result<Network, Error> {
+        let response = self.get("/info").await?;
+
+        match response.status() {
+            StatusCode::OK => Ok(response.json::<Network>().await.map_err(Error::from)?),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    pub async fn get_bundle(&self, id: &str) -> Result<(BundleTx, Bundle), Error> {
+        let (tx, stream) = self.stream_bundle(id).await?;
+        let bundle = stream.into_bundle().await?;
+
+        Ok((tx, bundle))
+    }
+
+    /// Like `get_bundle`, but the items are parsed as the data arrives.
+    pub async fn stream_bundle(&self, id: &str) -> Result<(BundleTx, VerifiedBundleStream), Error> {
+        let tx = self.get_tx(id).await?;
+        tx.is_valid()?;
+
+        let stream = self.stream_bundle_data(&tx).await?;
+
+        Ok((tx, stream))
+    }
+
+    /// Requests the transaction header, without checking it is a bundle.
+    pub async fn get_tx(&self, id: &str) -> Result<BundleTx, Error> {
+        let response = self.get(&format!("/tx/{}", id)).await?;
+
+        match response.status() {
+            StatusCode::OK => Ok(response.json::<BundleTx>().await.map_err(Error::from)?),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    pub async fn get_bundle_data(&self, tx: &BundleTx) -> Result<Bundle, Error> {
+        self.stream_bundle_data(tx).await?.into_bundle().await
+    }
+
+    /// Requests the bundle data and returns a stream of its items, parsed as
+    /// the data arrives. The data is hashed along the way and checked against
+    /// the transaction `data_root` by `VerifiedBundleStream::verify`.
+    pub async fn stream_bundle_data(&self, tx: &BundleTx) -> Result<VerifiedBundleStream, Error> {
+        let size = tx.data_size_bytes()?;
+        let expected = tx.data_root_bytes()?;
+
+        let reader = ChunkHashingReader::new(self.get_bundle_reader(tx).await?, size);
+        let stream = BundleStream::new(reader, &self.tags_schema, &self.parse_options).await?;
+
+        Ok(VerifiedBundleStream {
+            stream,
+            data_root: tx.data_root.clone(),
+            expected,
+        })
+    }
+
+    /// Reader over the bundle data, from the chunk endpoints in chunked mode
+    /// or when only the peers hold the data.
+    async fn get_bundle_reader(&self, tx: &BundleTx) -> Result<BodyReader, Error> {
+        match self.chunked {
+            true => self.get_chunked_data(tx).await,
+            false => match self.get_data_reader(tx).await {
+                // Nodes have no data endpoint, so peers are reached by chunks.
+                Err(e) if e.is_gateway_failure() && !self.peers.is_empty() => {
+                    warn!("bundle data {} unavailable on the gateways ({e}), trying peers", tx.id);
+                    self.get_chunked_data(tx).await
+                }
+                result => result,
+            },
+        }
+    }
+
+    /// Fetches a single item of a bundle with range requests, downloading the
+    /// header table and then only the byte span of the item.
+    /// The returned bundle holds just that item.
+    pub async fn get_bundle_item(&self, bundle_id: &str, item_id: &str) -> Result<Bundle, Error> {
+        let count = self.get_range(bundle_id, 0, 32).await?;
+        let items_len = bundle::items_len(&count)?;
+        let headers_end = bundle::headers_end(items_len)?;
+        let headers = self.get_range(bundle_id, 32, headers_end).await?;
+
+        let mut offset = headers_end;
+        for (index, entry) in headers.chunks_exact(64).enumerate() {
+            let entry = entry.try_into().map_err(|_| bundle::Error::ItemHeaderIncomplete)?;
+            let header = ItemHeader::from_bytes(entry)?;
+
+            if header.id != item_id {
+                offset = offset.saturating_add(header.length);
+                continue;
+            }
+
+            let end = offset.checked_add(header.length).ok_or(bundle::Error::ItemDataIncomplete)?;
+            let item_data = self.get_range(bundle_id, offset, end).await?;
+            let (item, diagnostics) = bundle::parse_item(
+                &item_data,
+                index,
+                &header,
+                offset,
+                &self.tags_schema,
+                &self.parse_options,
+            )?;
+
+            return Ok(Bundle {
+                items: vec![item],
+                diagnostics,
+            });
+        }
+
+        Err(Error::ItemNotFound {
+            bundle_id: bundle_id.to_string(),
+            item_id: item_id.to_string(),
+        })
+    }
+
+    /// Requests the `start..end` bytes of a resource.
+    async fn get_range(&self, path: &str, start: usize, end: usize) -> Result<Bytes, Error> {
+        if start == end {
+            return Ok(Bytes::new());
+        }
+
+        let range = format!("bytes={}-{}", start, end - 1);
+        let headers =
+            HeaderMap::from_iter([(RANGE, HeaderValue::from_str(&range).expect("ascii range"))]);
+        let response = self.send(Method::GET, path, headers, None).await?;
+
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                let bytes = response.bytes().await.map_err(Error::from)?;
+                match bytes.len() == end - start {
+                    true => Ok(bytes),
+                    false => Err(Error::RangeLengthMismatch {
+                        expected: end - start,
+                        found: bytes.len(),
+                    }),
+                }
+            }
+            // The whole resource is being sent, which is what ranges avoid.
+            StatusCode::OK => Err(Error::RangeNotSupported),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    async fn get_data_reader(&self, tx: &BundleTx) -> Result<BodyReader, Error> {
+        let response = self.get(&tx.id).await?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let body = response.bytes_stream().map_err(io::Error::other).boxed();
+                Ok(StreamReader::new(body))
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::bundle::merkle::data_root;
+    use crate::bundle::tags::Base64Tags;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const TEST_BUNDLE_TX: &str = "aJ3PrkyJ6GpdwwUxxXFHiB40cEg-GPRUWcKUI6wCgPQ";
+
+    /// Bundle transaction carrying the size and data root of `data`.
+    pub(crate) fn bundle_tx(id: &str, data: &[u8]) -> BundleTx {
+        BundleTx {
+            format: 2,
+            id: id.to_string(),
+            last_tx: String::new(),
+            owner: String::new(),
+            tags: Base64Tags(vec![]),
+            target: String::new(),
+            quantity: "0".to_string(),
+            data: String::new(),
+            data_root: base64.encode(data_root(data)),
+            data_size: data.len().to_string(),
+            reward: "0".to_string(),
+            signature: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_bundle() {
+        let client = Client::new(DEFAULT_BASE_URL.to_string(), DEFAULT_TIMEOUT_MS).unwrap();
+        let (bundle_tx, bundle) =
+            client.get_bundle(TEST_BUNDLE_TX).await.unwrap_or_else(|e| panic!("{:?}", e));
+
+        assert_eq!(bundle_tx.format, 2);
+        assert_eq!(bundle_tx.id, "aJ3PrkyJ6GpdwwUxxXFHiB40cEg-GPRUWcKUI6wCgPQ");
+        assert_eq!(
+            bundle_tx.last_tx,
+            "2iAqen10b8K0lVB3xmtp8plsd0GZzrc_yAoG8C9Fccz67Zc9U0vsvvP2S3S7tMtN"
+        );
+
+        assert_eq!(bundle.items.len(), 122);
+        assert_eq!(bundle.items[0].id, "eWABlTtLgOcrcWHWJNRBGSBSRmwN9_Rlm_IetJuir3o");
+    }
+
+    #[tokio::test]
+    async fn test_get_bundle_item() {
+        let test_bundle = std::fs::read("res/test_bundle").unwrap();
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let expected = Bundle::new(&test_bundle, &schema).unwrap();
+
+        let server = MockServer::start().await;
+        let first = ItemHeader::from_bytes(test_bundle[32..96].try_into().unwrap()).unwrap();
+        let item_start = 32 + 2 * 64 + first.length;
+        // The count and the header table are requested for both lookups below.
+        let ranges = [(0, 32, 2), (32, 160, 2), (item_start, test_bundle.len(), 1)];
+        for (start, end, requests) in ranges {
+            Mock::given(method("GET"))
+                .and(path(format!("/{TEST_BUNDLE_TX}")))
+                .and(header("Range", format!("bytes={}-{}", start, end - 1)))
+                .respond_with(
+                    ResponseTemplate::new(206).set_body_bytes(test_bundle[start..end].to_vec()),
+                )
+                .expect(requests)
+                .mount(&server)
+                .await;
+        }
+
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap();
+        let bundle = client.get_bundle_item(TEST_BUNDLE_TX, &expected.items[1].id).await.unwrap();
+        assert!(bundle.items[0] == expected.items[1]);
+
+        let result = client.get_bundle_item(TEST_BUNDLE_TX, "missing").await;
+        assert!(matches!(result, Err(Error::ItemNotFound { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_get_bundle_item_without_ranges() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![0; 64]))
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap();
+        let result = client.get_bundle_item(TEST_BUNDLE_TX, "item").await;
+        assert!(matches!(result, Err(Error::RangeNotSupported)));
+    }
+
+    #[tokio::test]
+    async fn test_get_bundle_data_verified() {
+        let test_bundle = std::fs::read("res/test_bundle").unwrap();
+        let schema = Schema::parse_str(TAGS_AVRO_SCHEMA).unwrap();
+        let expected = Bundle::new(&test_bundle, &schema).unwrap();
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(format!("/{TEST_BUNDLE_TX}")))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(test_bundle.clone()))
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap();
+        let bundle =
+            client.get_bundle_data(&bundle_tx(TEST_BUNDLE_TX, &test_bundle)).await.unwrap();
+        assert!(bundle.items == expected.items);
+
+        let mut tampered = test_bundle.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        let result = client.get_bundle_data(&bundle_tx(TEST_BUNDLE_TX, &tampered)).await;
+        assert!(matches!(result, Err(Error::DataRootMismatch { .. })));
+
+        // Items of the streamed data parse fine, only the final check fails.
+        let tx = bundle_tx(TEST_BUNDLE_TX, &tampered);
+        let mut stream = client.stream_bundle_data(&tx).await.unwrap();
+        while let Some(item) = stream.next_item().await {
+            assert!(item.is_ok());
+        }
+        assert!(matches!(stream.verify().await, Err(Error::DataRootMismatch { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_limited_requests() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let client = Client::new(DEFAULT_BASE_URL.to_string(), DEFAULT_TIMEOUT_MS)
+            .unwrap()
+            .with_concurrency(2);
+        let (running, peak) = (AtomicUsize::new(0), AtomicUsize::new(0));
+
+        // Clones share the same slots.
+        let requests = (0..6).map(|_| {
+            let client = client.clone();
+            let (running, peak) = (&running, &peak);
+            async move {
+                client
+                    .limited(async {
+                        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                        peak.fetch_max(now, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+                        running.fetch_sub(1, Ordering::SeqCst);
+                        Ok::<_, Error>(())
+                    })
+                    .await
+            }
+        });
+        futures::future::try_join_all(requests).await.unwrap();
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_client_errors() {
+        let result = Client::new("not a url".to_string(), DEFAULT_TIMEOUT_MS);
+        assert!(matches!(result, Err(Error::UrlError(_))));
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/info"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("not found"))
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS).unwrap();
+        match client.get_network_info().await {
+            Err(Error::StatusError {
+                status,
+                message,
+            }) => {
+                assert_eq!(status, StatusCode::NOT_FOUND);
+                assert_eq!(message, "not found");
+            }
+            result => panic!("unexpected result: {:?}", result.map(|info| info.to_string())),
+        }
+    }
+}
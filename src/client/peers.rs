@@ -0,0 +1,313 @@
+use crate::bundle::network::Network;
+use crate::client::{Client, Error};
+use futures::StreamExt;
+use reqwest::{Response, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+use url::Url;
+
+/// Peers probed at the same time during discovery.
+pub const PROBE_CONCURRENCY: usize = 16;
+
+/// Peers tried for a request the gateways could not serve.
+pub const MAX_PEER_ATTEMPTS: usize = 3;
+
+/// Blocks a peer may lag behind the highest known peer and still be used.
+const MAX_HEIGHT_LAG: u64 = 5;
+
+/// Consecutive failures after which a peer is no longer used.
+const MAX_PEER_FAILURES: u32 = 3;
+
+/// Last known state of an Arweave node, as persisted between runs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PeerInfo {
+    /// `ip:port` as listed by `/peers`.
+    pub address: String,
+    pub height: u64,
+    /// Round trip of the last `/info` probe.
+    pub latency_ms: u64,
+    /// Failures since the last success.
+    pub failures: u32,
+    /// Unix time of the last success.
+    pub last_seen: u64,
+}
+
+impl PeerInfo {
+    pub fn url(&self) -> Result<Url, url::ParseError> {
+        Url::parse(&format!("http://{}", self.address))
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// Known peers, shared across clones of the pool.
+#[derive(Debug, Clone, Default)]
+pub struct PeerPool {
+    peers: Arc<Mutex<HashMap<String, PeerInfo>>>,
+}
+
+impl PeerPool {
+    pub fn new(peers: impl IntoIterator<Item = PeerInfo>) -> Self {
+        let peers = peers.into_iter().map(|peer| (peer.address.clone(), peer)).collect();
+
+        Self {
+            peers: Arc::new(Mutex::new(peers)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.lock().unwrap().is_empty()
+    }
+
+    /// Every known peer, usable or not, for persisting the pool.
+    pub fn snapshot(&self) -> HashMap<String, PeerInfo> {
+        self.peers.lock().unwrap().clone()
+    }
+
+    pub fn insert(&self, peer: PeerInfo) {
+        self.peers.lock().unwrap().insert(peer.address.clone(), peer);
+    }
+
+    /// Usable peers, fastest first. Failing peers and peers lagging behind the
+    /// highest one are left out.
+    pub fn ranked(&self) -> Vec<PeerInfo> {
+        let peers = self.peers.lock().unwrap();
+        let best_height = peers.values().map(|peer| peer.height).max().unwrap_or_default();
+
+        let mut ranked: Vec<PeerInfo> = peers
+            .values()
+            .filter(|peer| peer.failures < MAX_PEER_FAILURES)
+            .filter(|peer| peer.height + MAX_HEIGHT_LAG >= best_height)
+            .cloned()
+            .collect();
+        ranked.sort_by_key(|peer| peer.latency_ms);
+        ranked
+    }
+
+    pub fn record_success(&self, address: &str) {
+        if let Some(peer) = self.peers.lock().unwrap().get_mut(address) {
+            peer.failures = 0;
+            peer.last_seen = unix_time();
+        }
+    }
+
+    pub fn record_failure(&self, address: &str) {
+        if let Some(peer) = self.peers.lock().unwrap().get_mut(address) {
+            peer.failures += 1;
+        }
+    }
+}
+
+impl Client {
+    /// Lists the peers known to the gateways, as `ip:port` addresses.
+    pub async fn get_peers(&self) -> Result<Vec<String>, Error> {
+        let response = self.get("/peers").await?;
+
+        match response.status() {
+            StatusCode::OK => Ok(response.json::<Vec<String>>().await.map_err(Error::from)?),
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Requests `/info` straight from a peer, measuring its latency.
+    pub async fn probe_peer(&self, address: &str) -> Result<PeerInfo, Error> {
+        let url = Url::parse(&format!("http://{address}/info"))?;
+        self.throttle().await;
+        let started = Instant::now();
+        let response = self.client.get(url).send().await.map_err(Error::from)?;
+
+        match response.status() {
+            StatusCode::OK => {
+                let network = response.json::<Network>().await.map_err(Error::from)?;
+                Ok(PeerInfo {
+                    address: address.to_string(),
+                    height: network.height as u64,
+                    latency_ms: started.elapsed().as_millis() as u64,
+                    failures: 0,
+                    last_seen: unix_time(),
+                })
+            }
+            _ => Err(Error::from_response(response).await),
+        }
+    }
+
+    /// Probes the known peers and up to `limit` new ones listed by the
+    /// gateways, adding the responsive ones to the peer pool.
+    /// Returns the number of usable peers.
+    pub async fn discover_peers(&self, limit: usize) -> Result<usize, Error> {
+        let known = self.peers.snapshot();
+        let listed = self.get_peers().await?;
+        let new: Vec<String> =
+            listed.into_iter().filter(|address| !known.contains_key(address)).take(limit).collect();
+        let addresses: Vec<String> = known.into_keys().chain(new).collect();
+
+        info!("probing {} peers", addresses.len());
+
+        let probes = futures::stream::iter(addresses)
+            .map(|address| async move { (self.probe_peer(&address).await, address) })
+            .buffer_unordered(PROBE_CONCURRENCY);
+        let results: Vec<(Result<PeerInfo, Error>, String)> = probes.collect().await;
+
+        for (result, address) in results {
+            match result {
+                Ok(peer) => self.peers.insert(peer),
+                Err(_) => self.peers.record_failure(&address),
+            }
+        }
+
+        Ok(self.peers.ranked().len())
+    }
+
+    /// Like `get`, but a request the gateways could not serve is sent to the
+    /// best ranked peers directly.
+    pub(crate) async fn get_with_peers(&self, path: &str) -> Result<Response, Error> {
+        let result = self.get(path).await;
+        if matches!(&result, Ok(response) if response.status().is_success()) {
+            return result;
+        }
+
+        for peer in self.peers.ranked().into_iter().take(MAX_PEER_ATTEMPTS) {
+            let url = match peer.url().and_then(|url| url.join(path)) {
+                Ok(url) => url,
+                Err(e) => {
+                    warn!("skipping peer {} with an invalid address: {e}", peer.address);
+                    self.peers.record_failure(&peer.address);
+                    continue;
+                }
+            };
+            self.throttle().await;
+
+            match self.client.get(url).send().await {
+                Ok(response) if response.status().is_success() => {
+                    self.peers.record_success(&peer.address);
+                    return Ok(response);
+                }
+                // The peer is fine, it just does not hold the data.
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("request to peer {} failed: {e}", peer.address);
+                    self.peers.record_failure(&peer.address);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::merkle::generate_proofs;
+    use crate::client::chunks::tests::mount_chunks;
+    use crate::client::retry::RetryPolicy;
+    use crate::client::tests::bundle_tx;
+    use crate::client::DEFAULT_TIMEOUT_MS;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn peer(address: &str, height: u64, latency_ms: u64) -> PeerInfo {
+        PeerInfo {
+            address: address.to_string(),
+            height,
+            latency_ms,
+            failures: 0,
+            last_seen: 0,
+        }
+    }
+
+    #[test]
+    fn test_peer_ranking() {
+        let pool = PeerPool::new([
+            peer("1.1.1.1:1984", 100, 300),
+            peer("2.2.2.2:1984", 98, 100),
+            peer("3.3.3.3:1984", 90, 10),
+            peer("4.4.4.4:1984", 100, 200),
+        ]);
+
+        let ranked: Vec<String> = pool.ranked().into_iter().map(|peer| peer.address).collect();
+        assert_eq!(ranked, ["2.2.2.2:1984", "4.4.4.4:1984", "1.1.1.1:1984"]);
+
+        for _ in 0..MAX_PEER_FAILURES {
+            pool.record_failure("2.2.2.2:1984");
+        }
+        assert_eq!(pool.ranked()[0].address, "4.4.4.4:1984");
+        assert_eq!(pool.snapshot().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_discover_peers() {
+        let node = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/info"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "network": "arweave.N.1",
+                "version": 5,
+                "release": 69,
+                "height": 1400000,
+                "current": "block",
+                "blocks": 1400001,
+                "peers": 100,
+                "queue_length": 0,
+                "node_state_latency": 1,
+            })))
+            .mount(&node)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/chunk/10"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("chunk"))
+            .mount(&node)
+            .await;
+
+        let node_address = node.address().to_string();
+        let gateway = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/peers"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json([node_address.as_str(), "127.0.0.1:1"]),
+            )
+            .mount(&gateway)
+            .await;
+
+        let client = Client::new(gateway.uri(), DEFAULT_TIMEOUT_MS).unwrap();
+        assert_eq!(client.discover_peers(10).await.unwrap(), 1);
+        assert_eq!(client.peers.ranked()[0].address, node_address);
+
+        // The gateway has no chunks, the peer serves them instead.
+        let response = client.get_with_peers("/chunk/10").await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "chunk");
+    }
+
+    #[tokio::test]
+    async fn test_get_bundle_data_from_peers() {
+        let test_bundle = std::fs::read("res/test_bundle").unwrap();
+        let node = MockServer::start().await;
+        mount_chunks(&node, "bundle", &test_bundle, &generate_proofs(&test_bundle)).await;
+
+        // Every gateway request fails, so the data and the chunks come from the peer.
+        let gateway = MockServer::start().await;
+        Mock::given(method("GET")).respond_with(ResponseTemplate::new(503)).mount(&gateway).await;
+
+        // The best ranked peer has an address that is not a valid url.
+        let peers = PeerPool::new([
+            peer("not a host:1984", 100, 10),
+            peer(&node.address().to_string(), 100, 20),
+        ]);
+        let client = Client::new(gateway.uri(), DEFAULT_TIMEOUT_MS)
+            .unwrap()
+            .with_retry_policy(RetryPolicy {
+                max_attempts: 1,
+                ..Default::default()
+            })
+            .with_peers(peers);
+
+        let bundle = client.get_bundle_data(&bundle_tx("bundle", &test_bundle)).await.unwrap();
+        assert_eq!(bundle.items.len(), 2);
+        assert!(client.peers.snapshot()["not a host:1984"].failures > 0);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::client::Client;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Lowest supported rate, one request every 1000 seconds. Slower rates would
+/// make a single wait longer than any request timeout.
+pub const MIN_RATE: f64 = 0.001;
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Token bucket allowing `rate` requests per second on average and bursts of
+/// up to `burst` requests, shared across clones.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+impl RateLimiter {
+    /// Returns `None` unless the rate is finite and at least `MIN_RATE`.
+    pub fn new(rate: f64, burst: u32) -> Option<Self> {
+        if !rate.is_finite() || rate < MIN_RATE {
+            return None;
+        }
+
+        let burst = burst.max(1) as f64;
+        let bucket = Bucket {
+            tokens: burst,
+            updated: Instant::now(),
+        };
+
+        Some(Self {
+            rate,
+            burst,
+            bucket: Arc::new(Mutex::new(bucket)),
+        })
+    }
+
+    /// Waits until a request may be sent.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                let now = Instant::now();
+                let refill = now.duration_since(bucket.updated).as_secs_f64() * self.rate;
+                bucket.tokens = (bucket.tokens + refill).min(self.burst);
+                bucket.updated = now;
+
+                if bucket.tokens >= 1.0 {
+                    bucket.tokens -= 1.0;
+                    return;
+                }
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.rate)
+            };
+
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+impl Client {
+    /// Waits for the rate limiter, if any, before sending a request.
+    pub(crate) async fn throttle(&self) {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limiter() {
+        for rate in [0.0, -1.0, MIN_RATE / 2.0, f64::NAN, f64::INFINITY] {
+            assert!(RateLimiter::new(rate, 1).is_none());
+        }
+
+        let limiter = RateLimiter::new(20.0, 2).unwrap();
+        let started = Instant::now();
+
+        limiter.acquire().await;
+        limiter.clone().acquire().await;
+        assert!(started.elapsed() < Duration::from_millis(20));
+
+        // The burst is spent, the next tokens come every 50ms.
+        limiter.acquire().await;
+        limiter.clone().acquire().await;
+        assert!(started.elapsed() >= Duration::from_millis(95));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::client::{Client, Error};
+use bytes::Bytes;
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Method, Response, StatusCode};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+pub const DEFAULT_RETRY_DELAY_MS: u64 = 500;
+pub const DEFAULT_RETRY_JITTER_MS: u64 = 250;
+
+/// Upper bound of a single wait, `Retry-After` included.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// How failed requests are retried. The n-th retry waits
+/// `base_delay * 2^(n - 1)` plus a random jitter, unless the gateway asks for
+/// a specific wait with `Retry-After`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Total attempts per request, 1 disables retries.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_jitter: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            base_delay: Duration::from_millis(DEFAULT_RETRY_DELAY_MS),
+            max_jitter: Duration::from_millis(DEFAULT_RETRY_JITTER_MS),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Wait before the given retry, starting at 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.base_delay.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)));
+        let jitter = rand::thread_rng().gen_range(0..=self.max_jitter.as_millis() as u64);
+
+        backoff.saturating_add(Duration::from_millis(jitter)).min(MAX_RETRY_DELAY)
+    }
+}
+
+/// Statuses worth another attempt, as the same request may succeed later.
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::REQUEST_TIMEOUT
+            | StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+fn is_transient_error(error: &reqwest::Error) -> bool {
+    error.is_connect() || error.is_timeout()
+}
+
+impl Error {
+    /// Whether the gateways could not serve a request, as they miss the data
+    /// or keep failing transiently, so peers may still serve it.
+    pub(crate) fn is_gateway_failure(&self) -> bool {
+        match self {
+            Error::StatusError {
+                status,
+                ..
+            } => *status == StatusCode::NOT_FOUND || is_transient_status(*status),
+            Error::RequestError(e) => is_transient_error(e),
+            _ => false,
+        }
+    }
+}
+
+/// `Retry-After` in seconds, the HTTP date form is not supported.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    let seconds = value.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_RETRY_DELAY))
+}
+
+impl Client {
+    pub(crate) async fn get(&self, path: &str) -> Result<Response, Error> {
+        self.send(Method::GET, path, HeaderMap::new(), None).await
+    }
+
+    /// Sends a request for the path to the gateways of the pool. Only
+    /// idempotent requests should be sent, as any of them may be repeated.
+    /// A gateway failing with a transient error, or missing the data with a
+    /// 404, hands the request over to the next one. Once every gateway failed,
+    /// the request is retried according to the retry policy.
+    /// The last response is returned as is, so callers still handle its status.
+    pub(crate) async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        headers: HeaderMap,
+        body: Option<Bytes>,
+    ) -> Result<Response, Error> {
+        let mut retry = 0;
+        let mut tried = vec![];
+
+        loop {
+            let index = self.gateways.select(&tried).ok_or(Error::NoGateways)?;
+            tried.push(index);
+
+            let url = self.gateways.url(index).join(path)?;
+            self.throttle().await;
+            let started = Instant::now();
+            let mut request =
+                self.client.request(method.clone(), url.clone()).headers(headers.clone());
+            if let Some(body) = &body {
+                request = request.body(body.clone());
+            }
+            let result = request.send().await;
+
+            let transient = match &result {
+                Ok(response) if response.status() == StatusCode::NOT_FOUND => false,
+                Ok(response) if is_transient_status(response.status()) => true,
+                Ok(_) => {
+                    self.gateways.record_success(index, started.elapsed());
+                    return result.map_err(Error::from);
+                }
+                Err(e) if is_transient_error(e) => true,
+                Err(_) => return result.map_err(Error::from),
+            };
+
+            match &result {
+                Ok(response) => warn!("request to {url} failed with status {}", response.status()),
+                Err(e) => warn!("request to {url} failed: {e}"),
+            }
+
+            // A missing item is not the gateway's fault, fresh data may not
+            // have reached it yet.
+            if transient {
+                self.gateways.record_failure(index);
+            }
+
+            if tried.len() < self.gateways.len() {
+                continue;
+            }
+
+            if !transient || retry + 1 >= self.retry_policy.max_attempts {
+                return result.map_err(Error::from);
+            }
+
+            retry += 1;
+            tried.clear();
+
+            let delay = result.ok().as_ref().and_then(retry_after);
+            tokio::time::sleep(delay.unwrap_or_else(|| self.retry_policy.delay(retry))).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::gateways::{GatewayPool, GatewayStrategy};
+    use crate::client::DEFAULT_TIMEOUT_MS;
+    use wiremock::matchers::method;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_jitter: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_jitter: Duration::from_millis(10),
+        };
+
+        for (retry, backoff) in [(1, 100), (2, 200), (3, 400)] {
+            let delay = policy.delay(retry).as_millis() as u64;
+            assert!((backoff..=backoff + 10).contains(&delay));
+        }
+        assert_eq!(policy.delay(40), MAX_RETRY_DELAY);
+    }
+
+    #[tokio::test]
+    async fn test_send_retries() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS)
+            .unwrap()
+            .with_retry_policy(fast_policy(3));
+        let response = client.get("/").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_send_gives_up() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(502))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = Client::new(server.uri(), DEFAULT_TIMEOUT_MS)
+            .unwrap()
+            .with_retry_policy(fast_policy(2));
+        let response = client.get("/").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_send_failover() {
+        let mut servers = vec![];
+        for status in [503, 404, 200] {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(status))
+                .expect(1)
+                .mount(&server)
+                .await;
+            servers.push(server);
+        }
+
+        let urls = servers.iter().map(|server| url::Url::parse(&server.uri()).unwrap()).collect();
+        let pool = GatewayPool::new(urls, GatewayStrategy::RoundRobin);
+        let client = Client::new(servers[0].uri(), DEFAULT_TIMEOUT_MS)
+            .unwrap()
+            .with_gateways(pool.clone())
+            .with_retry_policy(fast_policy(1));
+
+        let response = client.get("/tx").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let health = pool.health();
+        assert_eq!(health[0].failures, 1);
+        assert_eq!(health[1].failures, 0);
+        assert!(health[2].latency.is_some());
+    }
+}
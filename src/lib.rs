@@ -0,0 +1,5 @@
+pub mod bundle;
+pub mod cache;
+pub mod cli;
+pub mod client;
+pub mod utils;
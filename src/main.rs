@@ -1,10 +1,5 @@
-mod bundle;
-mod cache;
-mod cli;
-mod client;
-mod utils;
-
-use crate::cli::Cli;
+use axer::cli;
+use axer::cli::Cli;
 use tracing::error;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::layer::SubscriberExt;
@@ -24,7 +19,7 @@ async fn main() -> Result<(), Error> {
         .with(EnvFilter::from_default_env().add_directive(LevelFilter::INFO.into()))
         .init();
 
-    let mut cli = Cli::from_env_args();
+    let mut cli = Cli::from_env_args()?;
 
     match cli.run().await {
         Ok(_) => Ok(()),
@@ -1,5 +1,8 @@
 use serde::Serialize;
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 use tracing::info;
 
 pub fn save_serde_json<T: Serialize>(path: PathBuf, data: &T) -> Result<(), std::io::Error> {
@@ -9,8 +12,113 @@ pub fn save_serde_json<T: Serialize>(path: PathBuf, data: &T) -> Result<(), std:
 
     info!("saving file to: {}", path.to_str().unwrap());
 
-    let json = serde_json::to_string(&data).unwrap();
-    std::fs::write(path, json)?;
+    write_json(&path, data)
+}
+
+/// Path of the file or directory written before it is moved to `path`.
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut partial = OsString::from(path.as_os_str());
+    partial.push(".partial");
+    PathBuf::from(partial)
+}
+
+/// Writes the JSON to the partial path and moves it to `path`, so an
+/// interrupted write never leaves a truncated file at the path.
+pub fn write_json<T: Serialize>(path: &Path, data: &T) -> Result<(), std::io::Error> {
+    let partial = partial_path(path);
+
+    let mut writer = BufWriter::new(File::create(&partial)?);
+    serde_json::to_writer(&mut writer, data)?;
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    drop(writer);
+
+    std::fs::rename(&partial, path)
+}
+
+/// Writes a JSON array to a file one element at a time.
+/// Elements go to `<path>.partial`, only renamed to the path by `finish`, so
+/// a file at the path is always complete. The partial file is removed when an
+/// unfinished writer is dropped.
+pub struct JsonArrayWriter {
+    path: PathBuf,
+    partial: PathBuf,
+    writer: BufWriter<File>,
+    empty: bool,
+}
+
+impl JsonArrayWriter {
+    pub fn create(path: PathBuf) -> Result<Self, std::io::Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let partial = partial_path(&path);
+
+        info!("saving file to: {}", path.display());
+
+        let mut writer = BufWriter::new(File::create(&partial)?);
+        writer.write_all(b"[")?;
+
+        Ok(Self {
+            path,
+            partial,
+            writer,
+            empty: true,
+        })
+    }
 
-    Ok(())
+    pub fn write<T: Serialize>(&mut self, value: &T) -> Result<(), std::io::Error> {
+        if !self.empty {
+            self.writer.write_all(b",")?;
+        }
+        self.empty = false;
+
+        serde_json::to_writer(&mut self.writer, value)?;
+        Ok(())
+    }
+
+    /// Closes the array and moves the file to its path.
+    pub fn finish(mut self) -> Result<(), std::io::Error> {
+        self.writer.write_all(b"]")?;
+        self.writer.flush()?;
+        std::fs::rename(&self.partial, &self.path)
+    }
+}
+
+impl Drop for JsonArrayWriter {
+    fn drop(&mut self) {
+        // Already renamed when the writer finished.
+        let _ = std::fs::remove_file(&self.partial);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_array_writer() {
+        let dir = std::env::temp_dir().join(format!("axer-json-{}", std::process::id()));
+        let path = dir.join("items.json");
+
+        let mut writer = JsonArrayWriter::create(path.clone()).unwrap();
+        writer.write(&1).unwrap();
+        writer.write(&"two").unwrap();
+        assert!(!path.exists());
+        writer.finish().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), r#"[1,"two"]"#);
+
+        // Unfinished writes leave neither the file nor the partial one.
+        std::fs::remove_file(&path).unwrap();
+        let mut writer = JsonArrayWriter::create(path.clone()).unwrap();
+        writer.write(&1).unwrap();
+        drop(writer);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        JsonArrayWriter::create(path.clone()).unwrap().finish().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[]");
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,90 @@
+/// File extension for a `Content-Type` value, ignoring its parameters.
+/// Generic binary types return `None`, so the data can be sniffed instead.
+pub fn extension_for_content_type(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next()?.trim().to_ascii_lowercase();
+
+    let extension = match mime.as_str() {
+        "image/png" => "png",
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "image/avif" => "avif",
+        "image/svg+xml" => "svg",
+        "application/json" => "json",
+        "application/pdf" => "pdf",
+        "application/zip" => "zip",
+        "application/gzip" => "gz",
+        "application/javascript" | "text/javascript" => "js",
+        "text/plain" => "txt",
+        "text/html" => "html",
+        "text/css" => "css",
+        "text/csv" => "csv",
+        "video/mp4" => "mp4",
+        "video/webm" => "webm",
+        "audio/mpeg" => "mp3",
+        _ => return None,
+    };
+
+    Some(extension)
+}
+
+/// File extension guessed from the leading magic bytes of the data.
+pub fn sniff_extension(data: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "png"),
+        (b"\xff\xd8\xff", "jpg"),
+        (b"GIF87a", "gif"),
+        (b"GIF89a", "gif"),
+        (b"%PDF-", "pdf"),
+        (b"PK\x03\x04", "zip"),
+        (b"\x1f\x8b", "gz"),
+        (b"ID3", "mp3"),
+        (b"\x1a\x45\xdf\xa3", "webm"),
+    ];
+
+    if let Some((_, extension)) = SIGNATURES.iter().find(|(magic, _)| data.starts_with(magic)) {
+        return Some(extension);
+    }
+
+    if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        return Some("webp");
+    }
+
+    if data.len() >= 12 && &data[4..8] == b"ftyp" {
+        return match &data[8..12] {
+            b"avif" => Some("avif"),
+            _ => Some("mp4"),
+        };
+    }
+
+    let trimmed = data.trim_ascii_start();
+    if (trimmed.starts_with(b"{") || trimmed.starts_with(b"["))
+        && serde_json::from_slice::<serde::de::IgnoredAny>(data).is_ok()
+    {
+        return Some("json");
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_for_content_type() {
+        assert_eq!(extension_for_content_type("image/png"), Some("png"));
+        assert_eq!(extension_for_content_type("Application/JSON; charset=utf-8"), Some("json"));
+        assert_eq!(extension_for_content_type("application/octet-stream"), None);
+    }
+
+    #[test]
+    fn test_sniff_extension() {
+        assert_eq!(sniff_extension(b"\x89PNG\r\n\x1a\n\0\0"), Some("png"));
+        assert_eq!(sniff_extension(b"RIFF\0\0\0\0WEBPVP8 "), Some("webp"));
+        assert_eq!(sniff_extension(b"\0\0\0\x20ftypisom"), Some("mp4"));
+        assert_eq!(sniff_extension(b" {\"a\": [1]}"), Some("json"));
+        assert_eq!(sniff_extension(b"{not json"), None);
+        assert_eq!(sniff_extension(b""), None);
+    }
+}
@@ -1,12 +1,88 @@
 use sha2::Digest;
+use std::fmt::{Display, Formatter};
 use thiserror::Error;
 
 pub mod file;
+pub mod mime;
 
 #[derive(Error, PartialEq, Debug)]
 pub enum Error {
     #[error("overflow error")]
     OverflowError,
+    #[error("value {value} exceeds the addressable size of {max}")]
+    NotAddressable {
+        value: U256,
+        max: usize,
+    },
+}
+
+/// Unsigned 256-bit integer, as used by ANS-104 for item counts and lengths.
+/// Stored as the 32 little-endian bytes it is read from.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct U256([u8; 32]);
+
+impl U256 {
+    /// Reads up to 32 little-endian bytes.
+    pub fn from_le_slice(slice: &[u8]) -> Result<Self, Error> {
+        if slice.len() > 32 {
+            return Err(Error::OverflowError);
+        }
+
+        let mut bytes = [0; 32];
+        bytes[..slice.len()].copy_from_slice(slice);
+        Ok(Self(bytes))
+    }
+
+    pub fn to_le_bytes(self) -> [u8; 32] {
+        self.0
+    }
+
+    /// Converts to a usize, failing if the value cannot be addressed by the host.
+    pub fn to_usize(self) -> Result<usize, Error> {
+        let width = std::mem::size_of::<usize>();
+
+        if self.0[width..].iter().any(|b| *b != 0) {
+            return Err(Error::NotAddressable {
+                value: self,
+                max: usize::MAX,
+            });
+        }
+
+        Ok(self.0[..width].iter().rev().fold(0, |num, b| num << 8 | *b as usize))
+    }
+}
+
+impl From<usize> for U256 {
+    fn from(value: usize) -> Self {
+        let mut bytes = [0; 32];
+        bytes[..std::mem::size_of::<usize>()].copy_from_slice(&value.to_le_bytes());
+        Self(bytes)
+    }
+}
+
+impl Display for U256 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Long division by 10 over the big-endian bytes, collecting the remainders.
+        let mut value: Vec<u8> = self.0.iter().rev().copied().collect();
+        let mut digits = vec![];
+
+        while value.iter().any(|b| *b != 0) {
+            let mut remainder = 0u16;
+            for byte in value.iter_mut() {
+                let acc = remainder << 8 | *byte as u16;
+                *byte = (acc / 10) as u8;
+                remainder = acc % 10;
+            }
+            digits.push(b'0' + remainder as u8);
+        }
+
+        if digits.is_empty() {
+            digits.push(b'0');
+        }
+
+        digits.reverse();
+        write!(f, "{}", String::from_utf8_lossy(&digits))
+    }
 }
 
 /// Convert a byte array to a u64.
@@ -33,10 +109,18 @@ pub fn sha256(message: &[u8]) -> [u8; 32] {
     result
 }
 
+pub fn sha384(message: &[u8]) -> [u8; 48] {
+    let mut context = sha2::Sha384::new();
+    context.update(message);
+    let mut result: [u8; 48] = [0; 48];
+    result.copy_from_slice(context.finalize().as_ref());
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::utils::Error::OverflowError;
+    use crate::utils::Error::{NotAddressable, OverflowError};
 
     #[test]
     fn test_byte_array_to_u64() {
@@ -59,6 +143,33 @@ mod tests {
         assert_eq!(byte_array_to_u64(&bytes), Err(OverflowError));
     }
 
+    #[test]
+    fn test_u256() {
+        let mut bytes = [0; 32];
+        bytes[0] = 100;
+        let value = U256::from_le_slice(&bytes).unwrap();
+        assert_eq!(value.to_usize(), Ok(100));
+        assert_eq!(value, U256::from(100));
+        assert_eq!(value.to_string(), "100");
+
+        let value = U256::from_le_slice(&[255; 32]).unwrap();
+        assert_eq!(
+            value.to_string(),
+            "115792089237316195423570985008687907853269984665640564039457584007913129639935"
+        );
+        assert_eq!(
+            value.to_usize(),
+            Err(NotAddressable {
+                value,
+                max: usize::MAX
+            })
+        );
+
+        assert_eq!(U256::from(usize::MAX).to_usize(), Ok(usize::MAX));
+        assert_eq!(U256::default().to_string(), "0");
+        assert_eq!(U256::from_le_slice(&[0; 33]), Err(OverflowError));
+    }
+
     #[test]
     fn test_sha256() {
         let message = b"hello world";
@@ -68,4 +179,15 @@ mod tests {
         ];
         assert_eq!(sha256(message), expected);
     }
+
+    #[test]
+    fn test_sha384() {
+        let message = b"hello world";
+        let expected = [
+            253, 189, 142, 117, 166, 127, 41, 247, 1, 164, 224, 64, 56, 94, 46, 35, 152, 99, 3,
+            234, 16, 35, 146, 17, 175, 144, 127, 203, 184, 53, 120, 179, 228, 23, 203, 113, 206,
+            100, 110, 253, 8, 25, 221, 140, 8, 141, 225, 189,
+        ];
+        assert_eq!(sha384(message), expected);
+    }
 }